@@ -0,0 +1,84 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use pentagame::graph::{
+    graph::{Graph, GraphState, ReplayMode, GRAPH},
+    models::{HAND, MOVE},
+    movegen::legal_moves,
+    notation::Position,
+    perft::perft,
+    rules::{self, Turn},
+};
+
+/*
+Benchmarks of the graph module (run with `cargo bench`)
+The replayed game is generated deterministically from the legal moves, so results are comparable
+between runs
+*/
+
+// moves (with fake GameMove ids) of a generated game between {players} players
+fn generate_game(players: u8, plies: usize) -> (GraphState, Vec<(i32, MOVE)>) {
+    let initial = GraphState::initial(players).unwrap();
+    let mut state = initial;
+    let mut turn = Turn::Move(1);
+    let mut moves: Vec<(i32, MOVE)> = Vec::with_capacity(plies);
+
+    for ply in 0..plies {
+        let pid = match turn {
+            Turn::Move(pid) | Turn::Placement(pid) => pid,
+            _ => break,
+        };
+
+        let options = legal_moves(&state, pid).unwrap();
+        let action = options[(ply * 7) % options.len()];
+        state = if rules::split(&action).1 == HAND {
+            rules::apply_placement(&state, &action).unwrap()
+        } else {
+            rules::apply_move(&state, pid, &action).unwrap().state
+        };
+
+        turn = match rules::ranking(&state, players) {
+            Some(ranking) => Turn::Finished(rules::winners(&ranking)),
+            None => rules::next_turn(&state, pid, players),
+        };
+        moves.push((ply as i32 + 1, action));
+    }
+
+    (initial, moves)
+}
+
+fn construct_graph(c: &mut Criterion) {
+    c.bench_function("construct_graph", |b| {
+        b.iter(|| Graph::construct_graph().unwrap())
+    });
+}
+
+fn validate(c: &mut Criterion) {
+    let mut graph = GRAPH.clone();
+    graph.load_state(GraphState::initial(5).unwrap()).unwrap();
+
+    // from a starting corner to the opposite junction
+    c.bench_function("validate", |b| {
+        b.iter(|| graph.validate(black_box(&[5, 0, 0]), black_box(&[2, 0, 0])))
+    });
+}
+
+fn replay(c: &mut Criterion) {
+    let (initial, moves) = generate_game(5, 200);
+
+    c.bench_function("replay trusted", |b| {
+        b.iter(|| GraphState::replay(&initial, black_box(&moves), ReplayMode::Trusted).unwrap())
+    });
+    c.bench_function("replay verify", |b| {
+        b.iter(|| GraphState::replay(&initial, black_box(&moves), ReplayMode::Verify).unwrap())
+    });
+}
+
+fn perft_initial(c: &mut Criterion) {
+    let position = Position::new(GraphState::initial(2).unwrap(), Turn::Move(1));
+
+    c.bench_function("perft 2", |b| {
+        b.iter(|| perft(black_box(&position), 2).unwrap())
+    });
+}
+
+criterion_group!(benches, construct_graph, validate, replay, perft_initial);
+criterion_main!(benches);
@@ -0,0 +1 @@
+ALTER TABLE game_moves DROP COLUMN hash;
@@ -0,0 +1,2 @@
+ALTER TABLE game_moves
+    ADD COLUMN hash BIGINT;
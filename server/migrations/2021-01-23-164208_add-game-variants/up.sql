@@ -0,0 +1,2 @@
+ALTER TABLE games
+    ADD COLUMN variant SMALLINT NOT NULL DEFAULT 0;
@@ -0,0 +1,6 @@
+CREATE TABLE chat_messages( id serial PRIMARY KEY,
+                           game_id INT REFERENCES games(id) NOT NULL,
+                           user_id UUID REFERENCES users(id) NOT NULL,
+                           channel SMALLINT NOT NULL,
+                           message TEXT NOT NULL,
+                           created_at TIMESTAMP NOT NULL DEFAULT NOW() );
@@ -0,0 +1,5 @@
+CREATE TABLE game_moderations( id serial PRIMARY KEY,
+                               game_id INT REFERENCES games(id) NOT NULL,
+                               user_id UUID REFERENCES users(id) NOT NULL,
+                               kind SMALLINT NOT NULL,
+                               UNIQUE (game_id, user_id, kind) );
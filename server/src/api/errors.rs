@@ -1,5 +1,6 @@
 // imports
-use crate::graph::errors::GraphErr;
+use crate::graph::errors::{GraphErr, NotationError, RecordError, RuleViolation};
+use crate::ws::errors::ChatError;
 use actix::dev::SendError;
 use actix_web::{
     dev::HttpResponseBuilder, error::BlockingError, error::Error as WebError, error::ResponseError,
@@ -82,6 +83,34 @@ impl From<GraphErr> for APIError {
     }
 }
 
+// Rule violations are the players fault and therefore validation errors
+impl From<RuleViolation> for APIError {
+    fn from(error: RuleViolation) -> APIError {
+        APIError::ValidationError(error.to_string())
+    }
+}
+
+// Chat messages are sent by clients (see ws/chat)
+impl From<ChatError> for APIError {
+    fn from(error: ChatError) -> APIError {
+        APIError::ValidationError(error.to_string())
+    }
+}
+
+// Positions are sent by clients -> invalid notation is a validation error
+impl From<NotationError> for APIError {
+    fn from(error: NotationError) -> APIError {
+        APIError::ValidationError(error.to_string())
+    }
+}
+
+// Records are replayed up to a ply chosen by the client (see graph/record replay_to)
+impl From<RecordError> for APIError {
+    fn from(error: RecordError) -> APIError {
+        APIError::ValidationError(error.to_string())
+    }
+}
+
 // convert WebErrors to APIErrors
 impl From<WebError> for APIError {
     fn from(error: WebError) -> APIError {
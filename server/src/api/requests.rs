@@ -6,6 +6,14 @@ pub struct GetGameRequest {
     pub id: i32,
 }
 
+#[derive(Deserialize)]
+pub struct GetGameAnalysisRequest {
+    // game id
+    pub id: i32,
+    // analyse the position after this many moves instead of the current one
+    pub ply: Option<usize>,
+}
+
 #[derive(Deserialize)]
 pub struct PostLoginRequest {
     // username
@@ -1,4 +1,9 @@
 use crate::db::model::Game;
+use crate::graph::{
+    models::MOVE,
+    notation::{format_move, Position},
+    search::Analysis,
+};
 use serde::Serialize;
 use uuid::Uuid;
 
@@ -8,6 +13,50 @@ pub struct GetGameResponse {
     pub users: Vec<(Uuid, String)>,
 }
 
+#[derive(Serialize)]
+pub struct GetGamePositionResponse {
+    // see graph/notation
+    pub position: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AnalysedMove {
+    // see graph/notation
+    pub notation: String,
+    pub action: MOVE,
+    // score of the moving player after the move
+    pub evaluation: i32,
+}
+
+// see graph/search Analysis
+#[derive(Serialize, Debug, Clone)]
+pub struct GetGameAnalysisResponse {
+    // see graph/notation
+    pub position: String,
+    // score of every player in seat order
+    pub evaluation: Vec<i32>,
+    // best moves first
+    pub moves: Vec<AnalysedMove>,
+}
+
+impl GetGameAnalysisResponse {
+    pub fn new(position: &Position, analysis: Analysis) -> GetGameAnalysisResponse {
+        GetGameAnalysisResponse {
+            position: position.to_string(),
+            evaluation: analysis.evaluation,
+            moves: analysis
+                .moves
+                .into_iter()
+                .map(|(action, evaluation)| AnalysedMove {
+                    notation: format_move(&action),
+                    action,
+                    evaluation,
+                })
+                .collect(),
+        }
+    }
+}
+
 #[derive(Serialize)]
 pub struct PostLoginResponse {
     pub authenticated: bool
@@ -1,10 +1,19 @@
 use super::errors::APIError;
-use super::requests::{GetGameRequest, PostLoginRequest};
-use super::responses::{GetGameResponse, PostLoginResponse};
+use super::requests::{GetGameAnalysisRequest, GetGameRequest, PostLoginRequest};
+use super::responses::{
+    GetGameAnalysisResponse, GetGamePositionResponse, GetGameResponse, PostLoginResponse,
+};
 use crate::auth::{guard_api, guard_api_with_user, verify_hash};
-use crate::db::actions::{get_game, get_user_alerts, get_user_by_username};
+use crate::db::actions::{get_game, get_user_alerts, get_user_by_username, is_practice_game};
 use crate::db::model::SlimUser;
 use crate::frontend::routes::DbPool;
+use crate::graph::{
+    graph::GraphState,
+    notation::Position,
+    record::GameRecord,
+    rules::Turn,
+    search::{analyse, analysis_allowed},
+};
 use actix_identity::Identity;
 use actix_web::{web::block, web::Data, web::Json, HttpResponse};
 
@@ -31,6 +40,97 @@ pub async fn get_game_meta(
     }
 }
 
+// current position of a public or own game in the position notation (see graph/notation)
+pub async fn get_game_position(
+    pool: Data<DbPool>,
+    data: Json<GetGameRequest>,
+    id: Option<SlimUser>,
+) -> APIResponse {
+    let user = guard_api_with_user(id)?;
+    let conn = pool.get()?;
+
+    let position = block(move || -> Result<String, APIError> {
+        let (game, users) = get_game(&conn, data.id)?;
+
+        // private games can only be viewed by their players
+        if !game.public && !users.iter().any(|(uid, _)| *uid == user.id) {
+            return Err(APIError::ValidationError("id".to_owned()));
+        }
+
+        let state = GraphState::build_from_db(&conn, game.id)?;
+        Ok(Position::from_game(state, game.state)?.to_string())
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(GetGamePositionResponse { position }))
+}
+
+// game record of a public or own game (see graph/record)
+pub async fn get_game_record(
+    pool: Data<DbPool>,
+    data: Json<GetGameRequest>,
+    id: Option<SlimUser>,
+) -> APIResponse {
+    let user = guard_api_with_user(id)?;
+    let conn = pool.get()?;
+
+    let record = block(move || -> Result<String, APIError> {
+        let (game, users) = get_game(&conn, data.id)?;
+
+        // private games can only be viewed by their players
+        if !game.public && !users.iter().any(|(uid, _)| *uid == user.id) {
+            return Err(APIError::ValidationError("id".to_owned()));
+        }
+
+        Ok(GameRecord::from_db(&conn, game.id)?.to_string())
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(record))
+}
+
+// best moves and evaluation of a finished or practice game (see graph/search analysis_allowed)
+pub async fn get_game_analysis(
+    pool: Data<DbPool>,
+    data: Json<GetGameAnalysisRequest>,
+    id: Option<SlimUser>,
+) -> APIResponse {
+    let user = guard_api_with_user(id)?;
+    let conn = pool.get()?;
+
+    let analysis = block(move || -> Result<GetGameAnalysisResponse, APIError> {
+        let (game, users) = get_game(&conn, data.id)?;
+
+        // private games can only be analysed by their players
+        if !game.public && !users.iter().any(|(uid, _)| *uid == user.id) {
+            return Err(APIError::ValidationError("id".to_owned()));
+        }
+
+        match Turn::from_state(game.state) {
+            Some(turn) if analysis_allowed(turn, is_practice_game(&conn, game.id)?) => (),
+            _ => {
+                return Err(APIError::AuthorizationError(
+                    "Analysis is only available for finished and practice games".to_owned(),
+                ))
+            }
+        };
+
+        let record = GameRecord::from_db(&conn, game.id)?;
+        let ply = data.ply.unwrap_or_else(|| record.moves.len());
+        let (position, last, history) = record.analysis_input(ply)?;
+
+        Ok(GetGameAnalysisResponse::new(
+            &position,
+            analyse(&position, &last, &history)?,
+        ))
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(analysis))
+}
+
 pub async fn post_login(
     id: Identity,
     pool: Data<DbPool>,
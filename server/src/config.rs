@@ -35,6 +35,12 @@ pub struct AuthenticationConfig {
 pub struct ServerConfig {
     pub ip: String,
     pub port: Option<u32>,
+    // board definition (see graph/board). The standard board is used if this is missing
+    pub board: Option<String>,
+    // seconds spectators see games later (see ws/actor). Spectators aren't delayed if this is missing
+    pub spectator_delay: Option<u64>,
+    // file with words blocked in the chat, one per line (see ws/chat WordFilter)
+    pub chat_filter: Option<String>,
 }
 
 #[derive(Deserialize, Clone, Serialize)]
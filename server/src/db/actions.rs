@@ -1,14 +1,18 @@
 // imports
 use super::helper::zero_trim;
-use super::model::{Game, NewGame, NewGameMove, NewUserGame, SlimUser, User, UserGame};
+use super::model::{
+    ChatMessage, Game, NewChatMessage, NewGame, NewGameModeration, NewGameMove, NewUserGame,
+    SlimUser, User, UserGame, BOT_PREFIX,
+};
 use super::schema::users;
 use crate::auth::generate_hash;
 use crate::graph::models::MOVE;
+use crate::graph::variant::Variant;
 use cached::{proc_macro::cached, stores::TimedCache};
 use chrono::offset::Local;
 use diesel::{
-    delete, insert_into, result::Error, BelongingToDsl, ExpressionMethods, JoinOnDsl,
-    OptionalExtension, PgConnection, QueryDsl, RunQueryDsl,
+    delete, insert_into, result::Error, update, BelongingToDsl, Connection, ExpressionMethods,
+    JoinOnDsl, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl,
 };
 use std::convert::TryInto;
 use uuid::Uuid;
@@ -19,6 +23,7 @@ pub fn create_game(
     description: Option<String>,
     public: bool,
     icon: String,
+    variant: i16,
     id: &SlimUser,
 ) -> Result<i32, Error> {
     use super::schema::{games, user_games};
@@ -36,6 +41,7 @@ pub fn create_game(
         public,
         icon: zero_trim(&icon),
         state: 0, // see state mapping in server/db/models
+        variant,
     };
 
     let gid = insert_into(games::table)
@@ -55,6 +61,54 @@ pub fn create_game(
     return Ok(gid);
 }
 
+// seat a new computer player in the game (see db/model User)
+pub fn add_bot(conn: &PgConnection, gid: i32, level: i16) -> Result<Uuid, Error> {
+    use super::schema::user_games;
+
+    let seat = user_games::table
+        .filter(user_games::game_id.eq(gid))
+        .count()
+        .get_result::<i64>(conn)?;
+
+    let bot = User {
+        id: Uuid::new_v4(),
+        username: format!("{}{}.{}", BOT_PREFIX, gid, seat + 1),
+        active: false,
+        password: String::new(),
+        status: "Computer player".to_owned(),
+        created_at: Local::now().naive_local(),
+        bot: Some(level),
+    };
+
+    insert_into(users::table).values(&bot).execute(conn)?;
+    insert_into(user_games::table)
+        .values(&NewUserGame {
+            game_id: gid,
+            user_id: bot.id,
+        })
+        .execute(conn)?;
+
+    Ok(bot.id)
+}
+
+// computer players of a game with their difficulty
+pub fn get_game_bots(conn: &PgConnection, gid: i32) -> Result<Vec<(Uuid, i16)>, Error> {
+    use super::schema::user_games::{self, dsl::game_id, dsl::user_id};
+    use super::schema::users::dsl::{bot, id};
+
+    user_games::table
+        .inner_join(users::table.on(id.eq(user_id)))
+        .filter(game_id.eq(gid))
+        .filter(bot.is_not_null())
+        .select((id, bot))
+        .load::<(Uuid, Option<i16>)>(conn)
+        .map(|bots| {
+            bots.into_iter()
+                .filter_map(|(uid, level)| level.map(|level| (uid, level)))
+                .collect()
+        })
+}
+
 pub fn check_game(conn: &PgConnection, id: i32) -> Result<i32, Error> {
     use super::schema::games;
 
@@ -176,6 +230,7 @@ pub fn create_user(
         id: Uuid::new_v4(),
         created_at: now,
         status,
+        bot: None,
     };
 
     let res = insert_into(users::table)
@@ -237,40 +292,89 @@ pub fn get_user_game(conn: &PgConnection, uid: Uuid) -> Result<Option<i32>, Erro
     }
 }
 
-pub fn leave_game(conn: &PgConnection, uid: Uuid) -> Result<(), Error> {
-    use super::schema::game_moves::{self, dsl::*};
-    use super::schema::user_games::{self, dsl::id as user_game_id, dsl::user_id};
-
-    /*
-    -> get latest game id
-        -> get latest user_games from all players -> take game_id and index
-    -> get latest figure_id
-        -> calculate figure ids -> remove all entries matching figure ids and game_id
-    -> Return
-    */
+// one human playing against computer players (see graph/search analysis_allowed)
+pub fn is_practice_game(conn: &PgConnection, gid: i32) -> Result<bool, Error> {
+    let bots = get_game_bots(conn, gid)?.len();
+    Ok(bots > 0 && get_game_seats(conn, gid)?.len() == bots + 1)
+}
 
-    let user_game = user_games::table
-        .filter(user_id.eq(&uid))
-        .select(user_games::all_columns)
-        .order_by(user_game_id)
-        .first::<UserGame>(conn)?;
+// seat order of a game: index + 1 is the pid used in game states and figure ids (see db/model)
+pub fn get_game_seats(conn: &PgConnection, gid: i32) -> Result<Vec<Uuid>, Error> {
+    use super::schema::user_games::dsl::*;
 
-    delete(user_games::table.filter(user_game_id.eq(user_game.id))).execute(conn)?;
-    delete(game_moves::table.filter(game_id.eq(user_game.game_id))).execute(conn)?;
+    user_games
+        .filter(game_id.eq(gid))
+        .order(id.asc())
+        .select(user_id)
+        .load::<Uuid>(conn)
+}
 
-    Ok(())
+/*
+gives up the seat in the current game (see get_user_game). Seats of running games are needed for the
+turn order and the record of the game. Returns false if the current game already started
+*/
+pub fn leave_game(conn: &PgConnection, uid: Uuid) -> Result<bool, Error> {
+    use super::schema::games::{self, dsl::state};
+    use super::schema::user_games::{self, dsl::user_id};
+
+    conn.transaction::<_, Error, _>(|| {
+        let seat = user_games::table
+            .inner_join(games::table)
+            .filter(user_id.eq(uid))
+            .filter(state.lt(11))
+            .select((user_games::id, state))
+            .for_update()
+            .first::<(i32, i16)>(conn)
+            .optional()?;
+
+        match seat {
+            Some((seat, game_state)) if can_leave(game_state) => {
+                delete(user_games::table.find(seat)).execute(conn)?;
+                Ok(true)
+            }
+            Some(_) => Ok(false),
+            None => Ok(true),
+        }
+    })
 }
 
-pub fn join_game(conn: &PgConnection, user_id: Uuid, game_id: i32) -> Result<(), Error> {
+// takes a seat in the game. Returns false if the game already started or all seats are taken
+pub fn join_game(conn: &PgConnection, user_id: Uuid, game_id: i32) -> Result<bool, Error> {
+    use super::schema::games::dsl::{games, state, variant};
     use super::schema::user_games;
 
-    let new_user_game = NewUserGame { game_id, user_id };
+    conn.transaction::<_, Error, _>(|| {
+        // the game is locked until the seat is taken (see start_game)
+        let (game_state, game_variant) = games
+            .find(game_id)
+            .select((state, variant))
+            .for_update()
+            .first::<(i16, i16)>(conn)?;
+        let seated = user_games::table
+            .filter(user_games::game_id.eq(game_id))
+            .count()
+            .get_result::<i64>(conn)?;
+
+        if !can_join(game_state, game_variant, seated) {
+            return Ok(false);
+        }
 
-    insert_into(user_games::table)
-        .values(&new_user_game)
-        .execute(conn)?;
+        insert_into(user_games::table)
+            .values(&NewUserGame { game_id, user_id })
+            .execute(conn)?;
+        Ok(true)
+    })
+}
 
-    Ok(())
+// seats only change while the game is waiting for players (see db/model Game)
+fn can_leave(game_state: i16) -> bool {
+    game_state == 0
+}
+
+// the variant of the game limits the amount of seats (see graph/variant)
+fn can_join(game_state: i16, game_variant: i16, seated: i64) -> bool {
+    let seats = Variant::from_id(game_variant).map_or(0, |variant| *variant.players().end());
+    can_leave(game_state) && seated < i64::from(seats)
 }
 
 #[cached(
@@ -331,7 +435,7 @@ pub fn fetch_latest_move(conn: &PgConnection, gid: i32, uid: Uuid, fid: i16) ->
         index += 1;
     });
 
-    action.1.drain(3..6).for_each(|location| {
+    action.1.drain(0..3).for_each(|location| {
         locations[index] = location;
         index += 1;
     });
@@ -350,10 +454,11 @@ pub fn make_new_move(
     user_id: Uuid,
     game_id: i32,
     action: MOVE,
+    hash: Option<i64>,
 ) -> Result<usize, Error> {
     use super::schema::game_moves;
 
-    let (src, dest) = action.0.split_at(2);
+    let (src, dest) = action.0.split_at(3);
 
     insert_into(game_moves::table)
         .values(NewGameMove {
@@ -362,6 +467,253 @@ pub fn make_new_move(
             dest,
             user_id,
             figure: action.1.into(),
+            hash,
         })
         .execute(conn)
 }
+
+// stored position hashes of all moves in insertion order (see db/model GameMove)
+pub fn get_game_hashes(conn: &PgConnection, gid: i32) -> Result<Vec<i64>, Error> {
+    use super::schema::game_moves::dsl::{game_id, game_moves, hash, id};
+
+    game_moves
+        .select(hash)
+        .filter(game_id.eq(gid))
+        .filter(hash.is_not_null())
+        .order_by(id.asc())
+        .load::<Option<i64>>(conn)
+        .map(|hashes| hashes.into_iter().flatten().collect())
+}
+
+// current games.state (uncached as it changes with every move)
+pub fn get_game_state(conn: &PgConnection, gid: i32) -> Result<i16, Error> {
+    use super::schema::games::dsl::{games, state};
+
+    games.find(gid).select(state).first::<i16>(conn)
+}
+
+// host starts a waiting game. Returns false if the user isn't the host or the game isn't waiting
+pub fn start_game(conn: &PgConnection, gid: i32, host: Uuid) -> Result<bool, Error> {
+    use super::schema::games::dsl::{games, id, state, user_id};
+
+    let updated = update(
+        games
+            .filter(id.eq(gid))
+            .filter(user_id.eq(host))
+            .filter(state.eq(0)),
+    )
+    .set(state.eq(1))
+    .execute(conn)?;
+
+    Ok(updated == 1)
+}
+
+// sets games.state if it still is {expected}. Returns false when another update came first
+pub fn advance_game_state(
+    conn: &PgConnection,
+    gid: i32,
+    expected: i16,
+    next: i16,
+) -> Result<bool, Error> {
+    use super::schema::games::dsl::{games, id, state};
+
+    let updated = update(games.filter(id.eq(gid)).filter(state.eq(expected)))
+        .set(state.eq(next))
+        .execute(conn)?;
+
+    Ok(updated == 1)
+}
+
+/*
+inserts the moves as (user, move, position hash) and advances games.state in one transaction
+The last move of a game is followed by the final ranking (see figure 42 in db/model)
+*/
+pub fn make_turn(
+    conn: &PgConnection,
+    game_id: i32,
+    moves: &[(Uuid, MOVE, Option<i64>)],
+    expected: i16,
+    next: i16,
+) -> Result<(), Error> {
+    conn.transaction::<_, Error, _>(|| {
+        if !advance_game_state(conn, game_id, expected, next)? {
+            // games.state changed in the meantime -> the move was made out of turn
+            return Err(Error::RollbackTransaction);
+        }
+
+        for (user_id, action, hash) in moves {
+            make_new_move(conn, *user_id, game_id, *action, *hash)?;
+        }
+        Ok(())
+    })
+}
+
+// kinds of db/model GameModerations
+pub const MUTED: i16 = 0;
+pub const KICKED: i16 = 1;
+
+/*
+kicks a user from a game for good (see db/model GameModerations). Players lose their seat, which is
+only possible before the game started. Returns false when the user plays in a running game
+*/
+pub fn kick_user(conn: &PgConnection, gid: i32, uid: Uuid) -> Result<bool, Error> {
+    use super::schema::games::dsl::{games, state};
+    use super::schema::user_games::dsl::{game_id, user_games, user_id};
+
+    conn.transaction::<_, Error, _>(|| {
+        let running = games.find(gid).select(state).first::<i16>(conn)? != 0;
+        let player = user_games
+            .filter(game_id.eq(gid))
+            .filter(user_id.eq(uid))
+            .count()
+            .get_result::<i64>(conn)?
+            > 0;
+        if running && player {
+            return Ok(false);
+        }
+
+        delete(user_games.filter(game_id.eq(gid)).filter(user_id.eq(uid))).execute(conn)?;
+        insert_moderation(conn, gid, uid, KICKED)?;
+        Ok(true)
+    })
+}
+
+pub fn set_muted(conn: &PgConnection, gid: i32, uid: Uuid, muted: bool) -> Result<(), Error> {
+    use super::schema::game_moderations::dsl::{game_id, game_moderations, kind, user_id};
+
+    if muted {
+        return insert_moderation(conn, gid, uid, MUTED);
+    }
+
+    delete(
+        game_moderations
+            .filter(game_id.eq(gid))
+            .filter(user_id.eq(uid))
+            .filter(kind.eq(MUTED)),
+    )
+    .execute(conn)?;
+    Ok(())
+}
+
+pub fn is_muted(conn: &PgConnection, gid: i32, uid: Uuid) -> Result<bool, Error> {
+    has_moderation(conn, gid, uid, MUTED)
+}
+
+pub fn is_kicked(conn: &PgConnection, gid: i32, uid: Uuid) -> Result<bool, Error> {
+    has_moderation(conn, gid, uid, KICKED)
+}
+
+fn has_moderation(
+    conn: &PgConnection,
+    gid: i32,
+    uid: Uuid,
+    moderation: i16,
+) -> Result<bool, Error> {
+    use super::schema::game_moderations::dsl::{game_id, game_moderations, kind, user_id};
+
+    let count = game_moderations
+        .filter(game_id.eq(gid))
+        .filter(user_id.eq(uid))
+        .filter(kind.eq(moderation))
+        .count()
+        .get_result::<i64>(conn)?;
+
+    Ok(count > 0)
+}
+
+// moderations are unique per game, user and kind -> repeating one changes nothing
+fn insert_moderation(
+    conn: &PgConnection,
+    gid: i32,
+    uid: Uuid,
+    moderation: i16,
+) -> Result<(), Error> {
+    use super::schema::game_moderations;
+
+    insert_into(game_moderations::table)
+        .values(&NewGameModeration {
+            game_id: gid,
+            user_id: uid,
+            kind: moderation,
+        })
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn get_game_host(conn: &PgConnection, gid: i32) -> Result<Uuid, Error> {
+    use super::schema::games::dsl::{games, user_id};
+
+    games.find(gid).select(user_id).first::<Uuid>(conn)
+}
+
+pub fn create_chat_message(
+    conn: &PgConnection,
+    gid: i32,
+    uid: Uuid,
+    channel: i16,
+    message: &str,
+) -> Result<ChatMessage, Error> {
+    use super::schema::chat_messages;
+
+    insert_into(chat_messages::table)
+        .values(&NewChatMessage {
+            game_id: gid,
+            user_id: uid,
+            channel,
+            message,
+        })
+        .get_result::<ChatMessage>(conn)
+}
+
+// last {limit} messages of a channel (see db/model ChatMessages) with their authors, oldest first
+pub fn get_chat_messages(
+    conn: &PgConnection,
+    gid: i32,
+    chat_channel: i16,
+    limit: i64,
+) -> Result<Vec<(ChatMessage, String)>, Error> {
+    use super::schema::chat_messages::{self, dsl::*};
+
+    let mut messages = chat_messages::table
+        .inner_join(users::table)
+        .filter(game_id.eq(gid))
+        .filter(channel.eq(chat_channel))
+        .order(id.desc())
+        .limit(limit)
+        .select((chat_messages::all_columns, users::username))
+        .load::<(ChatMessage, String)>(conn)?;
+
+    messages.reverse();
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seats_are_only_taken_in_waiting_games() {
+        let standard = Variant::Standard.id();
+        assert!(can_join(0, standard, 1));
+        assert!(can_join(0, standard, 4));
+        // all seats are taken
+        assert!(!can_join(0, standard, 5));
+        assert!(!can_join(0, Variant::Teams.id(), 4));
+        // running and finished games
+        assert!(!can_join(1, standard, 2));
+        assert!(!can_join(7, standard, 2));
+        assert!(!can_join(11, standard, 2));
+        // unknown variants have no seats
+        assert!(!can_join(0, 9, 0));
+    }
+
+    #[test]
+    fn seats_are_only_given_up_in_waiting_games() {
+        assert!(can_leave(0));
+        assert!(!can_leave(1));
+        assert!(!can_leave(8));
+        assert!(!can_leave(12));
+    }
+}
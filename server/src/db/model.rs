@@ -10,14 +10,20 @@ Gamemoves:
     Gamemoves contain information about moves (due to name conflict often called actions) done by players
     To allow actions such as placing a stopper the figure ids are used as below:
         1-25: player figures (1-5: first player with lowest UserGame.id, 6-10, second player …)
-        26-36: 5 gray, 5 black stoppers
+        26-35: 5 gray (26-30), 5 black stoppers (31-35)
     Stoppers are 'moved' (~placed) by the player colliding with them.
     Black stoppers are then moved to a new position, where no other figure is present (validation done based on graph state)
     Gray stoppers are moved to [src.0, src.1, src.2, -1, -1, -1] to mark them as 'off board'
+    Taken stoppers are held at [-2, -2, -2] until they're placed (see graph/rules)
 
-    When figure id == 42 -> umove: [player_figure, points, -1, -1, -1, -1]
+    When figure id == 42 -> umove: [pid, points, rank, -1, -1, -1]
     This is used to allow for point saving without extra column
-    All other GameMoves will be removed at the end of the game
+    Ties share a rank. The moves of a finished game are kept to allow replays
+    Figure ids 43 and 44 mark a computer or absent player passing their turn and resigning ([-1, -1, -1, -1, -1, -1])
+
+    hash is the zobrist hash of the position after the move (see graph/zobrist) stored as i64
+    It's used for repetition detection and is NULL for score moves, resignations and moves made
+    before it existed. Passes store the hash of the position with the next player to move
 */
 #[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
 #[table_name = "game_moves"]
@@ -30,6 +36,7 @@ pub struct GameMove {
     pub dest: Vec<i16>,
     pub user_id: Uuid,
     pub figure: i16,
+    pub hash: Option<i64>,
 }
 
 /*
@@ -57,14 +64,39 @@ pub struct Alert {
     pub message: String,
 }
 
+/*
+ChatMessages:
+    Messages of the chat of a game (see ws/chat). They're kept after the game finished
+
+    channel: i16/ smallint -> who can read the message (see ws/chat Channel)
+                0: players of the game
+                1: spectators of the game
+*/
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
+#[table_name = "chat_messages"]
+#[belongs_to(Game)]
+#[belongs_to(User)]
+pub struct ChatMessage {
+    pub id: i32,
+    pub game_id: i32,
+    pub user_id: Uuid,
+    pub channel: i16,
+    pub message: String,
+    pub created_at: NaiveDateTime,
+}
+
 /*
 States:
-    Player 1-5 = pid. This is order is based around the `rank` attribute of the UserGame
+    Player 1-5 = pid. This order is based around the `id` attribute of the UserGame (see db/actions get_game_seats)
 
     - 0 (not running): Waiting for players to join
     - 1-5 (pid): Waiting for move of {pid}
     - 6-10 (pid-5): Waiting for {pid} to set stopper
     - 11-16 (10 + winner amount) (finished): ranking is changed so that winners are at the top. Winner amount is the used for
+      The ranking itself is stored as figure 42 GameMoves (see above)
+
+Variants:
+    variant is the rule set of the game (see graph/variant Variant). 0 is the standard game
 */
 #[derive(Identifiable, Serialize, Queryable, Associations, Clone, PartialEq, Debug)]
 #[belongs_to(User)]
@@ -76,6 +108,7 @@ pub struct Game {
     pub state: i16,
     pub public: bool,
     pub icon: String,
+    pub variant: i16,
 }
 
 #[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
@@ -96,6 +129,16 @@ pub struct UserGame {
     pub game_id: i32,
 }
 
+/*
+Users:
+    bot is the difficulty of computer players (see graph/search Difficulty) and NULL for humans
+    Computer players are created for a single game (see db/actions add_bot). They're inactive and
+    their password isn't a valid hash, so nobody can log in as them. Their names start with
+    BOT_PREFIX, which humans can't register (see frontend/helper check_username)
+*/
+// non-ASCII and therefore never part of the name of a human
+pub const BOT_PREFIX: &str = "🤖 Bot ";
+
 #[derive(Identifiable, Insertable, Clone, Queryable, PartialEq, Debug)]
 pub struct User {
     pub id: Uuid,
@@ -104,6 +147,7 @@ pub struct User {
     pub password: String,
     pub status: String,
     pub created_at: NaiveDateTime,
+    pub bot: Option<i16>,
 }
 
 // Specializations
@@ -127,6 +171,7 @@ pub struct NewGame {
     pub state: i16,
     pub public: bool,
     pub icon: String,
+    pub variant: i16,
 }
 
 #[derive(Deserialize, Insertable)]
@@ -144,6 +189,32 @@ pub struct NewGameMove<'a> {
     pub dest: &'a [i16], // size: 3
     pub user_id: Uuid,
     pub figure: i16,
+    pub hash: Option<i64>,
+}
+
+#[derive(Insertable)]
+#[table_name = "chat_messages"]
+pub struct NewChatMessage<'a> {
+    pub game_id: i32,
+    pub user_id: Uuid,
+    pub channel: i16,
+    pub message: &'a str,
+}
+
+/*
+GameModerations:
+    Users the host of a game muted or kicked (see ws/chat). They're kept after the game finished
+
+    kind: i16/ smallint -> what the host did (see db/actions MUTED and KICKED)
+                0: the user can't send chat messages
+                1: the user can't join, rejoin or watch the game
+*/
+#[derive(Insertable)]
+#[table_name = "game_moderations"]
+pub struct NewGameModeration {
+    pub game_id: i32,
+    pub user_id: Uuid,
+    pub kind: i16,
 }
 
 // Conversion Support
@@ -7,6 +7,26 @@ table! {
     }
 }
 
+table! {
+    chat_messages (id) {
+        id -> Int4,
+        game_id -> Int4,
+        user_id -> Uuid,
+        channel -> Int2,
+        message -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    game_moderations (id) {
+        id -> Int4,
+        game_id -> Int4,
+        user_id -> Uuid,
+        kind -> Int2,
+    }
+}
+
 table! {
     game_moves (id) {
         id -> Int4,
@@ -15,6 +35,7 @@ table! {
         dest -> Array<Int2>,
         user_id -> Uuid,
         figure -> Int2,
+        hash -> Nullable<Int8>,
     }
 }
 
@@ -27,6 +48,7 @@ table! {
         state -> Int2,
         public -> Bool,
         icon -> Text,
+        variant -> Int2,
     }
 }
 
@@ -46,10 +68,15 @@ table! {
         password -> Text,
         status -> Text,
         created_at -> Timestamp,
+        bot -> Nullable<Int2>,
     }
 }
 
 joinable!(alerts -> users (user_id));
+joinable!(chat_messages -> games (game_id));
+joinable!(chat_messages -> users (user_id));
+joinable!(game_moderations -> games (game_id));
+joinable!(game_moderations -> users (user_id));
 joinable!(game_moves -> games (game_id));
 joinable!(game_moves -> users (user_id));
 joinable!(games -> users (user_id));
@@ -58,6 +85,8 @@ joinable!(user_games -> users (user_id));
 
 allow_tables_to_appear_in_same_query!(
     alerts,
+    chat_messages,
+    game_moderations,
     game_moves,
     games,
     user_games,
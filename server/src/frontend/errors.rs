@@ -1,6 +1,7 @@
 // imports
 use super::routes::{redirect, UserResponse};
 use super::templates;
+use crate::graph::errors::{GraphErr, RecordError};
 use actix_web::{
     dev::HttpResponseBuilder, error::BlockingError, error::ResponseError, http::header,
     http::StatusCode, Error as WebError, HttpResponse,
@@ -117,6 +118,20 @@ impl From<ParseError> for UserError {
     }
 }
 
+// Convert internal graph errors to UserErrors
+impl From<GraphErr> for UserError {
+    fn from(error: GraphErr) -> UserError {
+        UserError::InternalError(error.to_string())
+    }
+}
+
+// Records are replayed up to a ply chosen by the user (see graph/record replay_to)
+impl From<RecordError> for UserError {
+    fn from(error: RecordError) -> UserError {
+        UserError::ValidationError(error.to_string())
+    }
+}
+
 // convert WebErrors to UserErrors
 impl From<WebError> for UserError {
     fn from(error: WebError) -> UserError {
@@ -5,7 +5,15 @@ pub struct GameForm {
     pub name: String,
     pub public: Option<String>,
     pub description: Option<String>,
-    pub icon: Option<String>
+    pub icon: Option<String>,
+    pub bots: Option<String>, // amount of computer players (0-4)
+    pub difficulty: Option<String>, // see graph/search Difficulty
+    pub variant: Option<String>,    // see graph/variant Variant
+}
+
+#[derive(Deserialize)]
+pub struct BoardQuery {
+    pub ply: Option<usize>, // position after this many moves (see graph/record replay_to)
 }
 
 #[derive(Deserialize)]
@@ -1,3 +1,5 @@
+use crate::db::model::BOT_PREFIX;
+
 pub fn check_password(password: &str) -> bool {
     if password.len() > 6_usize {
         // check by going over chars and checking if one number, one uppercase and on lowercase is satisfied
@@ -31,3 +33,11 @@ pub fn check_password(password: &str) -> bool {
         return true;
     }
 }
+
+// same as check_password: true if the username can't be used. Names of computer players are reserved
+pub fn check_username(username: &str) -> bool {
+    username.is_empty()
+        || username.len() > 40_usize
+        || !username.is_ascii()
+        || username.starts_with(BOT_PREFIX)
+}
@@ -1,23 +1,29 @@
 // imports
 use super::errors::UserError;
-use super::helper::check_password;
+use super::helper::{check_password, check_username};
 use super::{forms, templates};
 use crate::auth::{generate_hash, guard_user, guard_with_user, verify_hash};
 use crate::db::actions::{
-    check_game, create_game, create_toast, create_user, get_cached_games, get_game, get_user_by_id,
-    get_user_by_username, get_user_game, join_game, leave_game,
+    add_bot, check_game, create_game, create_toast, create_user, get_cached_games, get_game,
+    get_user_by_id, get_user_by_username, get_user_game, is_kicked, join_game, leave_game,
 };
 use crate::db::helper::zero_trim;
 use crate::db::model::SlimUser;
+use crate::graph::graph::GraphState;
+use crate::graph::record::GameRecord;
+use crate::graph::render::render;
+use crate::graph::rules::Turn;
+use crate::graph::search::Difficulty;
+use crate::graph::variant::Variant;
 use actix_identity::Identity;
 use actix_web::error::ErrorBadRequest;
 use actix_web::{
     dev::HttpResponseBuilder, dev::Payload, http::header, http::StatusCode, web::block, web::Data,
-    web::Form, web::Path, Error, FromRequest, HttpRequest, HttpResponse,
+    web::Form, web::Path, web::Query, Error, FromRequest, HttpRequest, HttpResponse,
 };
 use askama_actix::TemplateIntoResponse;
 use diesel::r2d2::ConnectionManager;
-use diesel::PgConnection;
+use diesel::{result::Error as DBError, Connection, PgConnection};
 use futures::future::{err, ok, Ready};
 use serde::Serialize;
 use serde_json::from_str;
@@ -123,7 +129,7 @@ INFO: All routes except overview require are guarded
     /create: get_create_game -> Simple form for creating a new game
     /view/{id}: get_view_game -> View of game and it's participants
     /join/{id}: get_join_game -> Make user join game and redirect to game 'playing' screen
-    /leave: Leave a game (a player may only join one game at a time. Can be changed anytime but works as architectural rate limiting)
+    /leave: Leave a game (a player may only join one game at a time. Can be changed until the game started but works as architectural rate limiting)
 */
 
 pub async fn get_game_join(
@@ -137,27 +143,75 @@ pub async fn get_game_join(
 
     let gid = block(move || check_game(&conn, path.0 .0)).await?;
 
+    // the host kicked the user (see ws/chat)
     let conn = pool.get()?;
+    let sacrifice = uid.id;
+    if block(move || is_kicked(&conn, gid, sacrifice)).await? {
+        return Err(UserError::AuthorizationError(
+            "You were kicked from this game".to_owned(),
+        ));
+    }
 
-    // check if user already joined game
-    let sacrifice = uid.id.clone();
-    match block(move || get_user_game(&conn, sacrifice)).await? {
-        Some(current_game_id) => {
-            if current_game_id != gid {
-                let sacrifice = uid.id.clone();
-                let conn = pool.get()?;
-                block(move || leave_game(&conn, sacrifice)).await?;
-                let conn = pool.get()?;
-                block(move || join_game(&conn, sacrifice, gid)).await?;
+    // seats only change while games wait for players (see db/actions join_game)
+    let conn = pool.get()?;
+    let sacrifice = uid.id;
+    block(move || -> Result<(), UserError> {
+        let seated = conn.transaction::<_, DBError, _>(|| {
+            let seated = match get_user_game(&conn, sacrifice)? {
+                Some(current_game_id) if current_game_id == gid => true,
+                Some(_) => leave_game(&conn, sacrifice)? && join_game(&conn, sacrifice, gid)?,
+                None => join_game(&conn, sacrifice, gid)?,
+            };
+
+            // the user keeps their old seat if the game can't be joined
+            if seated {
+                Ok(())
+            } else {
+                Err(DBError::RollbackTransaction)
             }
+        });
+
+        match seated {
+            Ok(_) => Ok(()),
+            Err(DBError::RollbackTransaction) => Err(UserError::AuthorizationError(
+                "Games can only be joined and left before they started".to_owned(),
+            )),
+            Err(why) => Err(why.into()),
         }
-        None => {
-            let conn = pool.get()?;
-            block(move || join_game(&conn, sacrifice, gid)).await?;
+    })
+    .await?;
+
+    UserError::wrap_template(
+        templates::GameBoardTemplate {
+            id: Some(uid),
+            watch: None,
         }
+        .into_response(),
+    )
+}
+
+// read-only board of a public game
+pub async fn get_game_watch(
+    path: Path<(i32,)>,
+    id: Option<SlimUser>,
+    pool: Data<DbPool>,
+) -> UserResponse {
+    let conn = pool.get()?;
+    let uid = guard_with_user(id)?;
+    let gid = path.into_inner().0;
+
+    let (game, _) = block(move || get_game(&conn, gid)).await?;
+    if !game.public {
+        return Err(UserError::NotFoundError());
     }
 
-    UserError::wrap_template(templates::GameBoardTemplate { id: Some(uid) }.into_response())
+    UserError::wrap_template(
+        templates::GameBoardTemplate {
+            id: Some(uid),
+            watch: Some(gid),
+        }
+        .into_response(),
+    )
 }
 
 pub async fn get_game_overview(id: Option<SlimUser>, pool: Data<DbPool>) -> UserResponse {
@@ -214,16 +268,40 @@ pub async fn post_create_game(
     }
     .to_owned();
 
+    let variant = data
+        .variant
+        .as_ref()
+        .and_then(|variant| variant.parse::<Variant>().ok())
+        .unwrap_or(Variant::Standard);
+
+    // computer players take the seats after the host
+    let bots = match data.bots.as_ref().and_then(|bots| bots.parse::<u8>().ok()) {
+        Some(bots) if bots < *variant.players().end() => bots,
+        _ => 0,
+    };
+    let difficulty = data
+        .difficulty
+        .as_ref()
+        .and_then(|difficulty| difficulty.parse::<Difficulty>().ok())
+        .unwrap_or(Difficulty::Medium);
+
     // freeing thread because diesel doesn't support async net
-    let gid = block(move || {
-        create_game(
+    let gid = block(move || -> Result<i32, DBError> {
+        let gid = create_game(
             &conn,
             data.name.clone(),
             data.description.clone(),
             public,
             icon,
+            variant.id(),
             &user,
-        )
+        )?;
+
+        for _ in 0..bots {
+            add_bot(&conn, gid, difficulty.level())?;
+        }
+
+        Ok(gid)
     })
     .await?;
 
@@ -254,6 +332,51 @@ pub async fn get_view_game(
     )
 }
 
+/*
+Board image of a game (see graph/render)
+    games/{id}/board.svg [GET] -> current position with the last move highlighted
+    games/{id}/board.svg?ply=N [GET] -> position after the first N moves
+    Public games are visible to everyone, private games only to their players
+*/
+pub async fn get_game_board(
+    path: Path<(i32,)>,
+    query: Query<forms::BoardQuery>,
+    id: Option<SlimUser>,
+    pool: Data<DbPool>,
+) -> UserResponse {
+    let conn = pool.get()?;
+    let gid = path.into_inner().0;
+    let uid = id.map(|user| user.id);
+
+    let svg = block(move || -> Result<String, UserError> {
+        let (game, users) = get_game(&conn, gid)?;
+
+        if !game.public && !users.iter().any(|(player, _)| Some(*player) == uid) {
+            return Err(UserError::NotFoundError());
+        }
+
+        match Turn::from_state(game.state) {
+            Some(Turn::Waiting) | None => match query.ply {
+                Some(ply) if ply > 0 => Err(UserError::ValidationError(
+                    "The game hasn't started yet".to_owned(),
+                )),
+                _ => Ok(render(&GraphState::initial_from_db(&conn, gid)?, None)),
+            },
+            _ => {
+                let record = GameRecord::from_db(&conn, gid)?;
+                let ply = query.ply.unwrap_or_else(|| record.moves.len());
+                let (position, _) = record.replay_to(ply)?;
+
+                let last = ply.checked_sub(1).map(|index| &record.moves[index]);
+                Ok(render(&position.state, last))
+            }
+        }
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().content_type("image/svg+xml").body(svg))
+}
+
 /*
 Authentication & User managment
     users/login [GET|POST] -> users_login
@@ -531,6 +654,19 @@ pub async fn post_settings_user(
 
     match data.0.username {
         Some(new_username) => {
+            if check_username(&new_username) {
+                return UserError::wrap_template(
+                    templates::UserSettingsTemplate {
+                        user,
+                        id: Some(slim_user),
+                        status_error: false,
+                        password_error: false,
+                        username_error: true,
+                    }
+                    .into_response(),
+                );
+            }
+
             let sacrifice_id = user.id;
             let sacrifice_new_username = new_username.clone();
             let conn = pool.get()?;
@@ -623,9 +759,7 @@ pub async fn post_register_user(
     form: Form<forms::UserForm>,
 ) -> UserResponse {
     // Validate fields
-    let username_error = form.username.len() > 40_usize
-        || form.username.len() < 1_usize
-        || !form.username.is_ascii();
+    let username_error = check_username(&form.username);
     let cookie_error = match &form.cookies {
         Some(content) => content != "on",
         None => true,
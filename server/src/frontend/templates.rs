@@ -78,6 +78,8 @@ pub struct UserSettingsTemplate {
 #[template(path = "games/game.html")]
 pub struct GameBoardTemplate {
     pub id: Option<SlimUser>,
+    // watched public game (see ws/routes spectator_route)
+    pub watch: Option<i32>,
 }
 
 #[derive(Template)]
@@ -1,3 +1,14 @@
 pub mod models;
 pub mod graph;
 pub mod errors;
+pub mod rules;
+pub mod movegen;
+pub mod perft;
+pub mod notation;
+pub mod record;
+pub mod bitboard;
+pub mod board;
+pub mod zobrist;
+pub mod search;
+pub mod variant;
+pub mod render;
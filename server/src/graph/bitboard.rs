@@ -0,0 +1,206 @@
+use super::errors::GraphErr;
+use super::graph::{Graph, GraphState, GRAPH};
+use super::models::FIELD;
+use hashbrown::HashMap;
+
+/*
+Bitboard:
+    Fixed index representation of the board for the hot paths (rules engine, move generation, bots)
+    Every field gets an index and with it a bit of a u128. The neighbours of every field are
+    precomputed as a mask, so searching paths and checking occupation don't need any allocation
+
+    Fields are indexed in the sorted order of their coordinates. This keeps the indices independent
+    of the HashMap order of Graph and makes iterating a mask yield sorted fields
+*/
+pub type Bits = u128;
+
+// maximum amount of fields a Bits mask can hold
+pub const MAX_FIELDS: usize = 128;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Board {
+    // field of every index
+    fields: Vec<FIELD>,
+    indices: HashMap<FIELD, usize>,
+    // neighbours of every index as mask
+    neighbours: Vec<Bits>,
+    // mask of all fields
+    all: Bits,
+}
+
+// occupation of a GraphState as masks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Occupancy {
+    // fields holding at least one figure
+    pub occupied: Bits,
+    // fields holding more than one figure (only starting corners)
+    pub crowded: Bits,
+}
+
+impl Board {
+    pub fn from_graph(graph: &Graph) -> Result<Board, GraphErr> {
+        let mut fields = graph.vertices.keys().copied().collect::<Vec<FIELD>>();
+        fields.sort();
+
+        if fields.len() > MAX_FIELDS {
+            return Err(GraphErr::CannotConstructState(format!(
+                "{} fields don't fit into a bitboard",
+                fields.len()
+            )));
+        }
+
+        let indices = fields
+            .iter()
+            .enumerate()
+            .map(|(index, field)| (*field, index))
+            .collect::<HashMap<FIELD, usize>>();
+
+        let mut neighbours: Vec<Bits> = Vec::with_capacity(fields.len());
+        for field in &fields {
+            let mut mask: Bits = 0;
+            for neighbour in graph.edges.get(field).into_iter().flatten() {
+                match indices.get(neighbour) {
+                    Some(index) => mask |= 1 << index,
+                    None => return Err(GraphErr::NoSuchVertex),
+                }
+            }
+            neighbours.push(mask);
+        }
+
+        let all = match fields.len() {
+            MAX_FIELDS => Bits::MAX,
+            amount => (1 << amount) - 1,
+        };
+
+        Ok(Board {
+            fields,
+            indices,
+            neighbours,
+            all,
+        })
+    }
+
+    pub fn index(&self, field: &FIELD) -> Option<usize> {
+        self.indices.get(field).copied()
+    }
+
+    pub fn field(&self, index: usize) -> Option<FIELD> {
+        self.fields.get(index).copied()
+    }
+
+    pub fn bit(&self, field: &FIELD) -> Option<Bits> {
+        self.index(field).map(|index| 1 << index)
+    }
+
+    pub fn neighbours(&self, index: usize) -> Bits {
+        self.neighbours.get(index).copied().unwrap_or(0)
+    }
+
+    // mask of every field of the board
+    pub fn all(&self) -> Bits {
+        self.all
+    }
+
+    // fields of a mask in sorted order
+    pub fn fields_of(&self, mask: Bits) -> impl Iterator<Item = FIELD> + '_ {
+        indices(mask).filter_map(move |index| self.field(index))
+    }
+
+    pub fn occupancy(&self, state: &GraphState) -> Result<Occupancy, GraphErr> {
+        let mut occupancy = Occupancy {
+            occupied: 0,
+            crowded: 0,
+        };
+
+        for (location, _) in state.figures() {
+            let bit = self.bit(&location).ok_or(GraphErr::NoSuchVertex)?;
+            occupancy.crowded |= occupancy.occupied & bit;
+            occupancy.occupied |= bit;
+        }
+
+        Ok(occupancy)
+    }
+
+    /*
+    every field that can be reached from {src} without crossing an occupied field
+    Occupied fields bordering the reached area are included (swap and stopper targets), src isn't
+    */
+    pub fn reachable(&self, src: usize, occupied: Bits) -> Bits {
+        let mut visited: Bits = 1 << src;
+        let mut frontier = visited;
+        let mut reached: Bits = 0;
+
+        while frontier != 0 {
+            let mut next: Bits = 0;
+            for index in indices(frontier) {
+                next |= self.neighbours(index);
+            }
+            next &= !visited;
+
+            visited |= next;
+            reached |= next;
+            // occupied fields can be reached but not passed
+            frontier = next & !occupied;
+        }
+
+        reached
+    }
+
+    /*
+    shortest route from {src} to {dest} (both included) as indices. Same rules as reachable: only
+    dest may be occupied. Ties between routes of equal length are broken by the lower index
+    */
+    pub fn path(&self, src: usize, dest: usize, occupied: Bits) -> Option<Vec<usize>> {
+        let target: Bits = 1 << dest;
+        let mut visited: Bits = 1 << src;
+        let mut frontier = visited;
+        // fields at distance n from src
+        let mut layers: Vec<Bits> = vec![visited];
+
+        while layers[layers.len() - 1] & target == 0 {
+            let mut next: Bits = 0;
+            for index in indices(frontier) {
+                next |= self.neighbours(index);
+            }
+            next &= !visited;
+
+            if next == 0 {
+                return None;
+            }
+
+            visited |= next;
+            layers.push(next);
+            frontier = next & !occupied;
+        }
+
+        // walk back through the layers. Only the first layer (src) may be occupied
+        let mut path: Vec<usize> = vec![dest];
+        for (distance, layer) in layers.iter().enumerate().rev().skip(1) {
+            let passable = match distance {
+                0 => *layer,
+                _ => *layer & !occupied,
+            };
+            let previous = indices(self.neighbours(path[path.len() - 1]) & passable).next()?;
+            path.push(previous);
+        }
+        path.reverse();
+
+        Some(path)
+    }
+}
+
+// indices of the set bits of a mask in ascending order
+pub fn indices(mut mask: Bits) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || match mask {
+        0 => None,
+        _ => {
+            let index = mask.trailing_zeros() as usize;
+            mask &= mask - 1;
+            Some(index)
+        }
+    })
+}
+
+lazy_static! {
+    pub static ref BOARD: Board = Board::from_graph(&GRAPH).expect("Failed to build bitboard");
+}
@@ -0,0 +1,315 @@
+use super::bitboard::MAX_FIELDS;
+use super::errors::BoardError;
+use hashbrown::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::read_to_string;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/*
+BoardDefinition:
+    Topology of the board as described by a board file (TOML or JSON, see boards/standard.toml).
+    The nodes are the junctions and corners, every line between two nodes is listed once with the
+    amount of stops on it. The stops of a line from a to b are the fields [a, 1..=stops, b]
+
+    Graph::construct_graph (see graph/graph) builds the board every game, the bitboard and the
+    notation are based on. It's the standard board unless another one was selected before
+    (see select). The server selects the board given as `board` in the [server] section of the config
+
+    The rules engine still expects the junctions 0-4 and the corners 5-9, so boards may only differ
+    in their lines and stops for now
+*/
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct BoardDefinition {
+    pub name: String,
+    pub junctions: Vec<i16>,
+    pub corners: Vec<i16>,
+    pub edges: Vec<EdgeDefinition>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EdgeDefinition {
+    pub from: i16,
+    pub to: i16,
+    pub stops: i16,
+}
+
+pub const STANDARD_BOARD: &str = include_str!("../../boards/standard.toml");
+
+impl BoardDefinition {
+    pub fn standard() -> BoardDefinition {
+        BoardDefinition::from_toml(STANDARD_BOARD).expect("Standard board is invalid")
+    }
+
+    pub fn from_toml(raw: &str) -> Result<BoardDefinition, BoardError> {
+        let definition = toml::from_str::<BoardDefinition>(raw)
+            .map_err(|why| BoardError::Malformed(why.to_string()))?;
+        definition.validate()?;
+        Ok(definition)
+    }
+
+    pub fn from_json(raw: &str) -> Result<BoardDefinition, BoardError> {
+        let definition = serde_json::from_str::<BoardDefinition>(raw)
+            .map_err(|why| BoardError::Malformed(why.to_string()))?;
+        definition.validate()?;
+        Ok(definition)
+    }
+
+    // files ending with .json are read as JSON, everything else as TOML
+    pub fn load(path: &Path) -> Result<BoardDefinition, BoardError> {
+        let raw = read_to_string(path)
+            .map_err(|why| BoardError::Malformed(format!("{}: {}", path.display(), why)))?;
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => BoardDefinition::from_json(&raw),
+            _ => BoardDefinition::from_toml(&raw),
+        }
+    }
+
+    // amount of fields (nodes and stops)
+    pub fn fields(&self) -> usize {
+        self.junctions.len()
+            + self.corners.len()
+            + self
+                .edges
+                .iter()
+                .map(|edge| edge.stops.max(0) as usize)
+                .sum::<usize>()
+    }
+
+    // (from, to, amount of stops) of the line between two nodes in the order it was defined in
+    pub fn line(&self, first: i16, second: i16) -> Option<(i16, i16, i16)> {
+        self.edges
+            .iter()
+            .find(|edge| {
+                (edge.from == first && edge.to == second)
+                    || (edge.from == second && edge.to == first)
+            })
+            .map(|edge| (edge.from, edge.to, edge.stops))
+    }
+
+    pub fn validate(&self) -> Result<(), BoardError> {
+        let mut nodes: HashSet<i16> = HashSet::new();
+        for node in self.junctions.iter().chain(self.corners.iter()) {
+            if !nodes.insert(*node) {
+                return Err(BoardError::DuplicateNode(*node));
+            }
+        }
+
+        if self.junctions.len() != self.corners.len() {
+            return Err(BoardError::NodeMismatch(
+                self.junctions.len(),
+                self.corners.len(),
+            ));
+        }
+
+        // lines are undirected -> a-b and b-a are the same line
+        let mut lines: HashSet<(i16, i16)> = HashSet::new();
+        let mut neighbours: HashMap<i16, Vec<i16>> = HashMap::new();
+        for edge in &self.edges {
+            if !nodes.contains(&edge.from) || !nodes.contains(&edge.to) {
+                return Err(BoardError::UnknownNode(edge.from, edge.to));
+            } else if edge.from == edge.to || edge.stops < 1 {
+                return Err(BoardError::InvalidEdge(edge.from, edge.to));
+            } else if !lines.insert((edge.from.min(edge.to), edge.from.max(edge.to))) {
+                return Err(BoardError::DuplicateEdge(edge.from, edge.to));
+            }
+
+            neighbours.entry(edge.from).or_default().push(edge.to);
+            neighbours.entry(edge.to).or_default().push(edge.from);
+        }
+
+        // every junction borders a corner and every corner a junction
+        let borders = |node: &i16, others: &[i16]| match neighbours.get(node) {
+            Some(adjacent) => adjacent.iter().any(|other| others.contains(other)),
+            None => false,
+        };
+        for junction in &self.junctions {
+            if !borders(junction, &self.corners) {
+                return Err(BoardError::Unmatched(*junction));
+            }
+        }
+        for corner in &self.corners {
+            if !borders(corner, &self.junctions) {
+                return Err(BoardError::Unmatched(*corner));
+            }
+        }
+
+        // stops only connect the two nodes of their line -> reaching every node is enough
+        if let Some(start) = self.junctions.first() {
+            let mut reached: HashSet<i16> = HashSet::new();
+            let mut queue: VecDeque<i16> = VecDeque::new();
+            reached.insert(*start);
+            queue.push_back(*start);
+
+            while let Some(node) = queue.pop_front() {
+                for neighbour in neighbours.get(&node).into_iter().flatten() {
+                    if reached.insert(*neighbour) {
+                        queue.push_back(*neighbour);
+                    }
+                }
+            }
+
+            let mut missing = nodes.difference(&reached).copied().collect::<Vec<i16>>();
+            missing.sort();
+            if let Some(node) = missing.first() {
+                return Err(BoardError::Disconnected(*node, *start));
+            }
+        }
+
+        if self.fields() > MAX_FIELDS {
+            return Err(BoardError::TooManyFields(self.fields()));
+        }
+
+        // see graph/rules start_of and goal_of
+        let mut junctions = self.junctions.clone();
+        let mut corners = self.corners.clone();
+        junctions.sort();
+        corners.sort();
+        if junctions != [0, 1, 2, 3, 4] || corners != [5, 6, 7, 8, 9] {
+            return Err(BoardError::UnsupportedNodes);
+        }
+
+        Ok(())
+    }
+}
+
+// DEFINITION was built -> selecting another board has no effect anymore
+static BUILT: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    // board used instead of the standard board (see select)
+    static ref SELECTED: Mutex<Option<BoardDefinition>> = Mutex::new(None);
+    // board every Graph is built from
+    pub static ref DEFINITION: BoardDefinition = {
+        BUILT.store(true, Ordering::SeqCst);
+        match SELECTED.lock().expect("Board selection was poisoned").take() {
+            Some(definition) => definition,
+            None => BoardDefinition::standard(),
+        }
+    };
+}
+
+/*
+Use another board than the standard board. This has to happen before the first graph is built
+(e.g. at the start of the server) because GRAPH, BOARD and the zobrist keys are only built once
+
+Games are stored without their board, so changing the board of a server invalidates its games
+*/
+pub fn select(definition: BoardDefinition) -> Result<(), BoardError> {
+    definition.validate()?;
+
+    if BUILT.load(Ordering::SeqCst) {
+        return Err(BoardError::AlreadyBuilt);
+    }
+
+    *SELECTED.lock().expect("Board selection was poisoned") = Some(definition);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOURNAMENT_BOARD: &str = include_str!("../../boards/tournament.toml");
+
+    fn edge(from: i16, to: i16, stops: i16) -> EdgeDefinition {
+        EdgeDefinition { from, to, stops }
+    }
+
+    #[test]
+    fn shipped_boards_are_valid() {
+        let standard = BoardDefinition::standard();
+        assert_eq!(standard.fields(), 100);
+        assert_eq!(standard.line(6, 0), Some((0, 6, 6)));
+        assert_eq!(standard.line(0, 2), None);
+
+        let tournament = BoardDefinition::from_toml(TOURNAMENT_BOARD).unwrap();
+        assert_eq!(tournament.fields(), 120);
+    }
+
+    #[test]
+    fn json_and_toml_agree() {
+        let standard = BoardDefinition::standard();
+        let json = serde_json::to_string(&standard).unwrap();
+        assert_eq!(BoardDefinition::from_json(&json).unwrap(), standard);
+    }
+
+    #[test]
+    fn invalid_boards() {
+        let check = |change: &dyn Fn(&mut BoardDefinition), expected: BoardError| {
+            let mut definition = BoardDefinition::standard();
+            change(&mut definition);
+            assert_eq!(definition.validate(), Err(expected));
+        };
+
+        check(&|board| board.corners[4] = 0, BoardError::DuplicateNode(0));
+        check(
+            &|board| board.corners.push(10),
+            BoardError::NodeMismatch(5, 6),
+        );
+        check(
+            &|board| board.edges.push(edge(0, 10, 3)),
+            BoardError::UnknownNode(0, 10),
+        );
+        check(
+            &|board| board.edges.push(edge(0, 0, 3)),
+            BoardError::InvalidEdge(0, 0),
+        );
+        check(
+            &|board| board.edges[0].stops = 0,
+            BoardError::InvalidEdge(0, 1),
+        );
+        check(
+            &|board| board.edges.push(edge(1, 0, 3)),
+            BoardError::DuplicateEdge(1, 0),
+        );
+        check(
+            &|board| board.edges.retain(|edge| edge.from != 0 || edge.to < 5),
+            BoardError::Unmatched(0),
+        );
+        check(
+            &|board| board.edges.retain(|edge| edge.from != 9 && edge.to != 9),
+            BoardError::Unmatched(9),
+        );
+        check(
+            &|board| {
+                board.edges = vec![
+                    edge(0, 5, 3),
+                    edge(1, 6, 3),
+                    edge(2, 7, 3),
+                    edge(3, 8, 3),
+                    edge(4, 9, 3),
+                ]
+            },
+            BoardError::Disconnected(1, 0),
+        );
+        check(
+            &|board| board.edges[0].stops = 32,
+            BoardError::TooManyFields(129),
+        );
+        check(
+            &|board| {
+                board.junctions = vec![10, 1, 2, 3, 4];
+                for edge in board.edges.iter_mut().filter(|edge| edge.from == 0) {
+                    edge.from = 10;
+                }
+            },
+            BoardError::UnsupportedNodes,
+        );
+    }
+
+    #[test]
+    fn malformed_files() {
+        assert!(matches!(
+            BoardDefinition::from_toml("junctions = 5"),
+            Err(BoardError::Malformed(_))
+        ));
+        assert!(matches!(
+            BoardDefinition::load(Path::new("boards/missing.toml")),
+            Err(BoardError::Malformed(_))
+        ));
+    }
+}
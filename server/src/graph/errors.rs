@@ -1,3 +1,4 @@
+use super::graph::Figure;
 use derive_more::Display;
 use diesel::result::Error as DBError;
 
@@ -20,6 +21,14 @@ pub enum GraphErr {
 
     // Couldn't construct State from database
     CannotConstructState(String),
+
+    // Replaying the move log failed at move (index, GameMove id, reason)
+    #[display(fmt = "Move {} (id {}) is inconsistent: {}", _0, _1, _2)]
+    InconsistentMove(usize, i32, RuleViolation),
+
+    // The board definition the graph is built from is invalid
+    #[display(fmt = "Invalid board: {}", _0)]
+    InvalidBoard(BoardError),
 }
 
 impl From<DBError> for GraphErr {
@@ -27,3 +36,114 @@ impl From<DBError> for GraphErr {
         GraphErr::CannotConstructState(error.to_string())
     }
 }
+
+/*
+RuleViolation:
+    returned by the rules engine (see graph/rules) when a move or stopper placement isn't allowed
+    The messages are shown to players as they are
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Display)]
+pub enum RuleViolation {
+    #[display(fmt = "Figure {} doesn't exist", _0)]
+    NoSuchFigure(Figure),
+    #[display(fmt = "Figure {} doesn't belong to you", _0)]
+    NotOwnFigure(Figure),
+    #[display(fmt = "Figure {} isn't on the board", _0)]
+    NotOnBoard(Figure),
+    #[display(fmt = "Figure {} isn't located at the given source", _0)]
+    SourceMismatch(Figure),
+    #[display(fmt = "The given field doesn't exist on the board")]
+    NoSuchField,
+    #[display(fmt = "A figure has to leave its field")]
+    SameField,
+    #[display(fmt = "There's no free path to the destination")]
+    NoFreePath,
+    #[display(fmt = "The destination is occupied by more than one figure")]
+    CrowdedField,
+    #[display(fmt = "The destination is already occupied")]
+    FieldOccupied,
+    #[display(fmt = "Figure {} belongs to your teammate and can't be swapped", _0)]
+    TeammateFigure(Figure),
+    #[display(fmt = "Figure {} isn't a stopper waiting to be placed", _0)]
+    NotPending(Figure),
+    #[display(fmt = "This move is repetitive")]
+    Repetition,
+    #[display(fmt = "This move would repeat the position for the third time")]
+    ThreefoldRepetition,
+    #[display(fmt = "A stopper has to be placed before moving again")]
+    PlacementPending,
+    #[display(fmt = "It's not your turn. Waiting for player {}", _0)]
+    OutOfTurn(u8),
+    #[display(fmt = "The game isn't running")]
+    NotRunning,
+    #[display(fmt = "The turn can't be passed because no other player can move")]
+    CannotPass,
+}
+
+/*
+NotationError:
+    returned when parsing the position, field or move notation (see graph/notation)
+*/
+#[derive(Clone, Debug, PartialEq, Eq, Display)]
+pub enum NotationError {
+    #[display(fmt = "Malformed position: {}", _0)]
+    Malformed(String),
+    #[display(fmt = "Expected 2 to 7 groups of figures but found {}", _0)]
+    InvalidGroupCount(usize),
+    #[display(fmt = "Group {} doesn't hold 5 valid locations", _0)]
+    InvalidGroup(usize),
+    #[display(fmt = "{} isn't a valid location", _0)]
+    InvalidField(String),
+    #[display(fmt = "{} isn't a valid move", _0)]
+    InvalidMove(String),
+    #[display(fmt = "{} isn't a valid turn", _0)]
+    InvalidTurn(String),
+    #[display(fmt = "Field {} holds more than one figure", _0)]
+    CrowdedField(String),
+}
+
+/*
+RecordError:
+    returned when parsing or replaying a game record (see graph/record). Moves are counted from 1
+*/
+#[derive(Clone, Debug, PartialEq, Eq, Display)]
+pub enum RecordError {
+    #[display(fmt = "Malformed record: {}", _0)]
+    Malformed(String),
+    #[display(fmt = "Move {} ({}) isn't valid move notation", _0, _1)]
+    InvalidMove(usize, String),
+    #[display(fmt = "Move {} ({}) is illegal: {}", _0, _1, _2)]
+    IllegalMove(usize, String, RuleViolation),
+    #[display(fmt = "The record claims the result {} but the moves lead to {}", _0, _1)]
+    ResultMismatch(String, String),
+}
+
+/*
+BoardError:
+    returned when loading or validating a board definition (see graph/board)
+*/
+#[derive(Clone, Debug, PartialEq, Eq, Display)]
+pub enum BoardError {
+    #[display(fmt = "Malformed board definition: {}", _0)]
+    Malformed(String),
+    #[display(fmt = "Node {} is defined more than once", _0)]
+    DuplicateNode(i16),
+    #[display(fmt = "{} junctions don't match {} corners", _0, _1)]
+    NodeMismatch(usize, usize),
+    #[display(fmt = "The rules require the junctions 0-4 and the corners 5-9")]
+    UnsupportedNodes,
+    #[display(fmt = "Line {}-{} connects an undefined node", _0, _1)]
+    UnknownNode(i16, i16),
+    #[display(fmt = "Line {}-{} needs two different nodes and at least one stop", _0, _1)]
+    InvalidEdge(i16, i16),
+    #[display(fmt = "Line {}-{} is defined more than once", _0, _1)]
+    DuplicateEdge(i16, i16),
+    #[display(fmt = "Node {} isn't connected to a node of the other kind", _0)]
+    Unmatched(i16),
+    #[display(fmt = "Node {} can't be reached from node {}", _0, _1)]
+    Disconnected(i16, i16),
+    #[display(fmt = "{} fields don't fit into a bitboard", _0)]
+    TooManyFields(usize),
+    #[display(fmt = "The board can't be changed after the graph was built")]
+    AlreadyBuilt,
+}
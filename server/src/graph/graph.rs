@@ -1,8 +1,11 @@
 // hash implmentations
-use super::errors::GraphErr;
-use super::models::{FIELD, LOCATION};
+use super::board::{BoardDefinition, DEFINITION};
+use super::errors::{GraphErr, RuleViolation};
+use super::models::{FIELD, HAND, LOCATION, MOVE, OFF_BOARD};
+use super::rules;
+use super::variant::Variant;
 use diesel::PgConnection;
-use hashbrown::{HashMap, HashSet};
+use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::convert::TryInto;
@@ -13,23 +16,17 @@ pub type Figure = u8;
 // State containing Positions of all figures (5 figures per player, 5 gray stoppers, 5 black stoppers)
 // LOCATION: ([i16; 3], u8)
 
-#[derive(Debug, Clone, Copy)]
-pub struct GraphState([LOCATION; 35]);
-
-// vertexmap
-pub const BASE_VERTEX_MAP: [i16; 10] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]; // in case the naming changes these are statically mapped
-pub const EDGE_MAP: [&[(i16, i16)]; 10] = [
-    &[(1, 3), (4, 3), (5, 6), (6, 6)],
-    &[(2, 3), (6, 6), (7, 6)],
-    &[(3, 3), (8, 6), (7, 6)],
-    &[(4, 3), (8, 6)],
-    &[(6, 5), (9, 6)],
-    &[(9, 3), (6, 3)],
-    &[(7, 3)],
-    &[(8, 3)],
-    &[(9, 3)],
-    &[(6, 6)],
-];
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphState([LOCATION; 35], Variant);
+
+// How strict GraphState::replay treats the move log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayMode {
+    // the database is a trusted source -> paths aren't searched again
+    Trusted,
+    // every move is validated by the rules engine
+    Verify,
+}
 
 #[derive(Deserialize, Serialize, Hash, PartialEq, Eq, Debug, Clone, Copy)]
 pub struct Field {
@@ -114,86 +111,124 @@ impl Graph {
 
     pub fn validate<'a>(&'a self, src: &'a FIELD, dest: &'a FIELD) -> Result<(bool, u8), GraphErr> {
         // check if specified vertices exists
-        let source = self.fetch(*src)?;
+        self.fetch(*src)?;
         let destination = self.fetch(*dest)?;
 
-        // test with dijkstra if there's a possible path
-        match self.bfs((source, src), (destination, dest)) {
-            Some(collider) => Ok((true, collider)),
-            None => Ok((false, 0)),
+        // test with bfs if there's a possible path
+        match self.bfs(src).1.contains_key(dest) {
+            true => Ok((true, destination.owner.unwrap_or(0))),
+            false => Ok((false, 0)),
         }
     }
 
-    fn bfs<'a>(&'a self, src: (&'a Field, &'a FIELD), dest: (&'a Field, &'a FIELD)) -> Option<u8> {
-        // Simple Breadth first search
-        // recommended explanation: https://www.programiz.com/dsa/graph-bfs
-
-        // WARNING: This will fail if dest.1.owner == None
+    /*
+    Simple Breadth first search from src
+    recommended explanation: https://www.programiz.com/dsa/graph-bfs
+    Returns (predecessor, distances) of every field that can be reached without crossing an
+    occupied field. Occupied fields bordering the reached area are included (swap and stopper
+    targets) but the search doesn't continue from them
+    */
+    fn bfs(&self, src: &FIELD) -> (HashMap<FIELD, FIELD>, HashMap<FIELD, u16>) {
         let v = self.vertices.len();
-        /*
-        Add this if you want to use this with more information
-        I may move to logic to an independent crate and this will be included in a
-        separate method
-        let mut predecessor: HashMap<FIELD, Vec<FIELD>> = HashMap::with_capacity(v);
+        let mut predecessor: HashMap<FIELD, FIELD> = HashMap::with_capacity(v);
         let mut distances: HashMap<FIELD, u16> = HashMap::with_capacity(v);
-        */
-        let mut visited: HashSet<FIELD> = HashSet::with_capacity(v);
         let mut queue: VecDeque<FIELD> = VecDeque::with_capacity(v);
 
-        // This step can't be skipped as the 'real' game field has already occupied fields
-        self.vertices.iter().for_each(|(id, f)| {
-            // mark occupied fields as already visited to prevent them from counting
-            if f.occupied {
-                visited.insert(*id);
-            }
+        // the source is occupied by the figure that moves -> it's always searched
+        distances.insert(*src, 0);
+        queue.push_back(*src);
 
-            /*
-            distances.insert(*id, u16::MAX);
-            */
-        });
+        while let Some(id) = queue.pop_front() {
+            let edges = match self.edges.get(&id) {
+                Some(edges) => edges,
+                None => continue,
+            };
+            let distance = distances[&id] + 1;
 
-        // mark src as already visited
-        visited.insert(*src.1);
-        // ^ this is just in case occupation on the source node wasn't handled
-        queue.push_back(*src.1);
+            for edge in edges {
+                if distances.contains_key(edge) {
+                    continue;
+                }
 
-        while !queue.is_empty() {
-            let id = queue.pop_front().unwrap();
-            println!("{:?}", id);
-            let edges = self.edges.get(&id).unwrap();
+                distances.insert(*edge, distance);
+                predecessor.insert(*edge, id);
 
-            for edge in edges {
-                let eq = edge == dest.1;
-                println!("QSize: {}", queue.len());
-                println!("Visited: {:?}", visited);
-                if eq {
-                    return dest.0.owner;
-                } else if !visited.contains(edge) {
-                    visited.insert(*edge);
+                // occupied fields can be reached but not passed
+                let occupied = matches!(self.vertices.get(edge), Some(field) if field.occupied);
+                if !occupied {
                     queue.push_back(*edge);
-                    /*
-                    distances[edge] = distances[id] + 1;
-                    predecessor.get_mut(edge).unwrap().push(F);
-                    */
                 }
             }
         }
 
-        None
+        (predecessor, distances)
+    }
+
+    // every field that can be reached from src without crossing occupied fields
+    // occupied fields are included when they border the reached area (swap and stopper targets)
+    pub fn reachable(&self, src: &FIELD) -> Result<Vec<FIELD>, GraphErr> {
+        self.fetch(*src)?;
+
+        Ok(self
+            .bfs(src)
+            .1
+            .into_iter()
+            .filter(|(_, distance)| *distance > 0)
+            .map(|(field, _)| field)
+            .collect())
+    }
+
+    // amount of steps to every reachable field (see reachable). src has the distance 0
+    pub fn distances(&self, src: &FIELD) -> Result<HashMap<FIELD, u16>, GraphErr> {
+        self.fetch(*src)?;
+        Ok(self.bfs(src).1)
+    }
+
+    /*
+    fields a figure passes when moving from src to dest (both included) on the shortest route
+    None if every route crosses an occupied field. Only dest itself may be occupied
+    */
+    pub fn shortest_path(&self, src: &FIELD, dest: &FIELD) -> Result<Option<Vec<FIELD>>, GraphErr> {
+        self.fetch(*src)?;
+        self.fetch(*dest)?;
+
+        let (predecessor, _) = self.bfs(src);
+        if src != dest && !predecessor.contains_key(dest) {
+            return Ok(None);
+        }
+
+        let mut path: Vec<FIELD> = vec![*dest];
+        while let Some(previous) = predecessor.get(&path[path.len() - 1]) {
+            path.push(*previous);
+        }
+        path.reverse();
+
+        Ok(Some(path))
     }
 
     pub fn load_state(&mut self, state: GraphState) -> Result<(), GraphErr> {
-        // TODO: Parallelize this someday
-        state.0.iter().for_each(|figure| {
-            self.figures_locations.insert(
-                Field {
-                    occupied: true,
-                    owner: Some(figure.1),
-                },
-                [figure.0[0], figure.0[1], figure.0[2]],
-            );
+        // reset occupation left over from a previous state
+        self.figures_locations.clear();
+        self.vertices.values_mut().for_each(|field| {
+            field.occupied = false;
+            field.owner = None;
         });
 
+        // TODO: Parallelize this someday
+        for (location, figure) in state.figures() {
+            let field = Field {
+                occupied: true,
+                owner: Some(figure),
+            };
+
+            match self.vertices.get_mut(&location) {
+                Some(vertex) => *vertex = field,
+                None => return Err(GraphErr::NoSuchVertex {}),
+            }
+
+            self.figures_locations.insert(field, location);
+        }
+
         Ok(())
     }
 
@@ -208,14 +243,19 @@ impl Graph {
     }
     */
 
+    // graph of the board every game is played on (see graph/board DEFINITION)
     pub fn construct_graph() -> Result<Graph, GraphErr> {
+        Graph::from_definition(&DEFINITION)
+    }
+
+    pub fn from_definition(definition: &BoardDefinition) -> Result<Graph, GraphErr> {
+        definition.validate().map_err(GraphErr::InvalidBoard)?;
         let mut graph: Graph = Graph::new();
-        let mut base_map: [FIELD; 10] = [[0, 0, 0]; 10];
 
         // the base nodes (junction, corners) need to be preinserted to do effective EDGE and stop mapping
-        for i in 0..BASE_VERTEX_MAP.len() {
-            base_map[i] = graph.add_vertex(
-                [BASE_VERTEX_MAP[i], 0, 0],
+        for node in definition.junctions.iter().chain(definition.corners.iter()) {
+            graph.add_vertex(
+                [*node, 0, 0],
                 Field {
                     occupied: false,
                     owner: None,
@@ -223,119 +263,353 @@ impl Graph {
             )?;
         }
 
-        // construct edges from edgemap. See pentagraph (python)
-        for index in 0..EDGE_MAP.len() {
-            let base_vertex = BASE_VERTEX_MAP[index];
-            let f_id = base_map[index];
-            for (svertex, vcounter) in EDGE_MAP[index] {
-                let mut s_id = graph.add_vertex(
-                    [base_vertex, 1, *svertex],
+        // construct the stops of every line (see boards/standard.toml)
+        for edge in &definition.edges {
+            let mut s_id = [edge.from, 0, 0];
+            for count in 1..edge.stops + 1 {
+                let t_id = graph.add_vertex(
+                    [edge.from, count, edge.to],
                     Field {
                         occupied: false,
                         owner: None,
                     },
                 )?;
 
-                let mut t_id = s_id; // This value is just to prevent warnings
-
-                graph.add_edge(f_id, s_id)?;
-                for count in 2..vcounter + 1 {
-                    t_id = graph.add_vertex(
-                        [base_vertex, count, *svertex],
-                        Field {
-                            occupied: false,
-                            owner: None,
-                        },
-                    )?;
-
-                    graph.add_edge(t_id, s_id)?;
-                    graph.add_edge(s_id, t_id)?;
+                graph.add_edge(t_id, s_id)?;
+                graph.add_edge(s_id, t_id)?;
 
-                    s_id = t_id;
-                }
-                graph.add_edge(t_id, [*svertex, 0, 0])?;
+                s_id = t_id;
             }
+            graph.add_edge(s_id, [edge.to, 0, 0])?;
+            graph.add_edge([edge.to, 0, 0], s_id)?;
         }
 
         // ensure only required space is used
         graph.shrink_to_fit();
 
-        return Ok(graph);
+        Ok(graph)
     }
 }
 
 impl GraphState {
-    // Creates an empty, as in no changes to the board but all player figures on board, state
-    pub fn empty() -> Result<GraphState, GraphErr> {
-        /*
-        This 'construction' is not optimized to allow for better understandability
-        It doesn't really matter anyway since it's saved in a lazy constant
-        */
-        let mut figures: [LOCATION; 35] = [([0_i16; 3], 0_u8); 35];
+    // all figures that are currently placed on a field of the board (empty slots have figure id 0)
+    pub fn figures(&self) -> impl Iterator<Item = LOCATION> + '_ {
+        self.0
+            .iter()
+            .copied()
+            .filter(|(location, figure)| *figure != 0 && is_on_board(location))
+    }
+
+    // current location of a figure
+    pub fn locate(&self, figure: Figure) -> Option<FIELD> {
+        match figure {
+            0 => None,
+            _ => self
+                .0
+                .iter()
+                .find(|(_, id)| *id == figure)
+                .map(|(location, _)| *location),
+        }
+    }
+
+    // figures standing on the given field (also works for OFF_BOARD and HAND)
+    pub fn occupants(&self, field: &FIELD) -> Vec<Figure> {
+        self.0
+            .iter()
+            .copied()
+            .filter(|(location, figure)| *figure != 0 && location == field)
+            .map(|(_, figure)| figure)
+            .collect::<Vec<Figure>>()
+    }
+
+    // amount of seated players (slots of players that aren't seated are empty)
+    pub fn players(&self) -> u8 {
+        (1..=5_u8)
+            .take_while(|pid| rules::figures_of(*pid).any(|figure| self.locate(figure).is_some()))
+            .count() as u8
+    }
+
+    // rule set the state is played with (see graph/variant)
+    pub fn variant(&self) -> Variant {
+        self.1
+    }
+
+    // move figure to a new location. Returns false if the figure isn't part of the state
+    pub fn relocate(&mut self, figure: Figure, location: FIELD) -> bool {
+        match self.0.iter_mut().find(|(_, id)| *id == figure && figure != 0) {
+            Some(slot) => {
+                slot.0 = location;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /*
+    Creates the state before the first move for {players} seated players
+        - slot n holds figure n + 1 (see graph/rules for the figure ids)
+        - player figures start on the corner of their color
+        - black stoppers start on the junctions (31 on junction 0, 32 on junction 1, ...)
+        - gray stoppers start off the board and come into play when figures reach their goal
+        - slots of players that aren't seated stay empty (figure id 0)
+    */
+    pub fn initial(players: u8) -> Result<GraphState, GraphErr> {
+        GraphState::initial_variant(players, Variant::Standard)
+    }
+
+    // same as initial but figures that don't take part in the variant stay empty as well
+    pub fn initial_variant(players: u8, variant: Variant) -> Result<GraphState, GraphErr> {
+        if players > 5 {
+            return Err(GraphErr::CannotConstructState(format!(
+                "{} players exceed the maximum of 5",
+                players
+            )));
+        }
+
+        let mut figures: [LOCATION; 35] = [(OFF_BOARD, 0_u8); 35];
 
         // adding players
-        (5..9).into_iter().for_each(|index| {
-            figures[index - 5] = ([index.try_into().unwrap(), 0, 0], index.try_into().unwrap());
-        });
+        for pid in 1..=players {
+            for figure in rules::figures_of(pid).filter(|figure| variant.plays(*figure)) {
+                if let Some(rules::FigureKind::Player { color, .. }) = rules::kind(figure) {
+                    figures[figure as usize - 1] = (rules::start_of(color), figure);
+                }
+            }
+        }
 
-        println!("{:?}", figures);
+        // adding stoppers
+        for figure in rules::GRAY_STOPPERS {
+            figures[figure as usize - 1] = (OFF_BOARD, figure);
+        }
+        for (junction, figure) in rules::BLACK_STOPPERS.enumerate() {
+            figures[figure as usize - 1] = ([junction as i16, 0, 0], figure);
+        }
 
-        Ok(GraphState(figures))
+        Ok(GraphState(figures, variant))
     }
 
-    // assemble GraphState from GameMoves. May return EMPTY_GRAPH when no GameMoves where done
-    pub fn build_from_db(conn: &PgConnection, gid: i32) -> Result<GraphState, GraphErr> {
-        // WARNING: Work in progress
+    /*
+    Replays moves (GameMove id, MOVE) in insertion order onto the given state
+    Player moves are applied by the owner of the figure, moves from HAND are stopper placements and
+    markers (score moves, passes and resignations, see graph/rules) are skipped. The first move that
+    can't be applied is returned as GraphErr::InconsistentMove
+    */
+    pub fn replay(
+        initial: &GraphState,
+        moves: &[(i32, MOVE)],
+        mode: ReplayMode,
+    ) -> Result<GraphState, GraphErr> {
+        let mut state = *initial;
+
+        for (index, (id, action)) in moves.iter().enumerate() {
+            let (figure, src, _) = rules::split(action);
+
+            let result = match rules::kind(figure) {
+                _ if rules::is_marker(figure) => continue,
+                _ if src == HAND => rules::apply_placement(&state, action),
+                Some(rules::FigureKind::Player { pid, .. }) => match mode {
+                    ReplayMode::Trusted => rules::replay_move(&state, pid, action),
+                    ReplayMode::Verify => rules::apply_move(&state, pid, action),
+                }
+                .map(|outcome| outcome.state),
+                Some(_) => Err(RuleViolation::NotPending(figure)),
+                None => Err(RuleViolation::NoSuchFigure(figure)),
+            };
+
+            state = match result {
+                Ok(next) => next,
+                Err(reason) => return Err(GraphErr::InconsistentMove(index, *id, reason)),
+            };
+        }
+
+        Ok(state)
+    }
+
+    // all moves of a game in insertion order
+    pub fn load_moves(conn: &PgConnection, gid: i32) -> Result<Vec<(i32, MOVE)>, GraphErr> {
         use crate::db::model::GameMove;
         use crate::db::schema::game_moves::{self, dsl::*};
         use diesel::prelude::*;
 
-        let mut new_state: GraphState = EMPTY_STATE.clone();
-        let mut base_data: Vec<GameMove> = match game_moves
+        let rows = game_moves
             .select(game_moves::all_columns)
             .filter(game_id.eq(gid))
-            .order_by(id.desc())
-            .limit(25)
-            .load::<GameMove>(conn)
-        {
-            Ok(data) => data,
-            Err(why) => {
-                eprintln!("GraphErr: {}", why);
-                return Err(GraphErr::CannotConstructState("DB Query faulty".to_owned()));
+            .order_by(id.asc())
+            .load::<GameMove>(conn)?;
+
+        let mut moves: Vec<(i32, MOVE)> = Vec::with_capacity(rows.len());
+        for (index, row) in rows.into_iter().enumerate() {
+            // the database doesn't enforce the array sizes
+            if row.src.len() != 3 || row.dest.len() != 3 {
+                return Err(GraphErr::InconsistentMove(
+                    index,
+                    row.id,
+                    RuleViolation::NoSuchField,
+                ));
             }
-        };
 
-        println!("First: {:?}", new_state.0);
+            let row_figure: Figure = match row.figure.try_into() {
+                Ok(row_figure) => row_figure,
+                Err(_) => {
+                    return Err(GraphErr::InconsistentMove(
+                        index,
+                        row.id,
+                        RuleViolation::NoSuchFigure(u8::MAX),
+                    ));
+                }
+            };
+
+            moves.push((
+                row.id,
+                (
+                    [
+                        row.src[0], row.src[1], row.src[2], row.dest[0], row.dest[1], row.dest[2],
+                    ],
+                    row_figure,
+                ),
+            ));
+        }
 
-        base_data.into_iter().enumerate().for_each(|(index, item)| {
-            // copy vector content into new array
-            let mut new_dest: [i16; 3] = [0; 3];
-            for y in 0..3 {
-                new_dest[y] = item.dest[y];
-            }
+        Ok(moves)
+    }
 
-            // create new figure state
-            println!("New State {} = {:?}", index, new_state.0[index]);
+    // initial state of a game based on its variant and the amount of seated players in user_games
+    pub fn initial_from_db(conn: &PgConnection, gid: i32) -> Result<GraphState, GraphErr> {
+        use crate::db::schema::games::dsl::{games, variant};
+        use crate::db::schema::user_games::dsl::{game_id, user_games};
+        use diesel::prelude::*;
 
-            new_state.0[index] = (
-                new_dest,
-                item.figure
-                    .try_into()
-                    .expect("Corrupted database entry for figure"),
-            );
+        let players = user_games
+            .filter(game_id.eq(gid))
+            .count()
+            .get_result::<i64>(conn)?;
+        let game_variant = games.find(gid).select(variant).first::<i16>(conn)?;
 
-            println!("New State {} = {:?}", index, new_state.0[index]);
-        });
+        let game_variant = Variant::from_id(game_variant).ok_or_else(|| {
+            GraphErr::CannotConstructState(format!("Unknown variant {}", game_variant))
+        })?;
 
-        println!("Second: {:?}", new_state.0);
+        // initial_variant rejects more than 5 players
+        GraphState::initial_variant(players.try_into().unwrap_or(u8::MAX), game_variant)
+    }
 
-        return Ok(new_state);
+    // assemble GraphState from all GameMoves. Returns the initial state when no GameMoves where done
+    pub fn build_from_db(conn: &PgConnection, gid: i32) -> Result<GraphState, GraphErr> {
+        let initial = GraphState::initial_from_db(conn, gid)?;
+        let moves = GraphState::load_moves(conn, gid)?;
+        GraphState::replay(&initial, &moves, ReplayMode::Trusted)
     }
+
+    // same as build_from_db but every move is validated by the rules engine
+    pub fn verify_from_db(conn: &PgConnection, gid: i32) -> Result<GraphState, GraphErr> {
+        let initial = GraphState::initial_from_db(conn, gid)?;
+        let moves = GraphState::load_moves(conn, gid)?;
+        GraphState::replay(&initial, &moves, ReplayMode::Verify)
+    }
+}
+
+// OFF_BOARD and HAND are the only fields outside the graph
+pub fn is_on_board(location: &FIELD) -> bool {
+    *location != OFF_BOARD && *location != HAND
 }
 
 // There's no need to construct the graph multiple times because it loads itself from a state
 lazy_static! {
-    pub static ref EMPTY_STATE: GraphState =
-        GraphState::empty().expect("Failed to build empty graph state");
     pub static ref GRAPH: Graph = Graph::construct_graph().expect("Failed to build empty graph");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn initial_state_for_five_players() {
+        let expected: [LOCATION; 35] = [
+            // player 1
+            ([5, 0, 0], 1),
+            ([6, 0, 0], 2),
+            ([7, 0, 0], 3),
+            ([8, 0, 0], 4),
+            ([9, 0, 0], 5),
+            // player 2
+            ([5, 0, 0], 6),
+            ([6, 0, 0], 7),
+            ([7, 0, 0], 8),
+            ([8, 0, 0], 9),
+            ([9, 0, 0], 10),
+            // player 3
+            ([5, 0, 0], 11),
+            ([6, 0, 0], 12),
+            ([7, 0, 0], 13),
+            ([8, 0, 0], 14),
+            ([9, 0, 0], 15),
+            // player 4
+            ([5, 0, 0], 16),
+            ([6, 0, 0], 17),
+            ([7, 0, 0], 18),
+            ([8, 0, 0], 19),
+            ([9, 0, 0], 20),
+            // player 5
+            ([5, 0, 0], 21),
+            ([6, 0, 0], 22),
+            ([7, 0, 0], 23),
+            ([8, 0, 0], 24),
+            ([9, 0, 0], 25),
+            // gray stoppers
+            (OFF_BOARD, 26),
+            (OFF_BOARD, 27),
+            (OFF_BOARD, 28),
+            (OFF_BOARD, 29),
+            (OFF_BOARD, 30),
+            // black stoppers
+            ([0, 0, 0], 31),
+            ([1, 0, 0], 32),
+            ([2, 0, 0], 33),
+            ([3, 0, 0], 34),
+            ([4, 0, 0], 35),
+        ];
+
+        assert_eq!(GraphState::initial(5).unwrap().0, expected);
+    }
+
+    #[test]
+    fn initial_state_leaves_empty_seats_empty() {
+        for players in 2..=5_u8 {
+            let state = GraphState::initial(players).unwrap();
+
+            for (index, (location, figure)) in state.0.iter().enumerate() {
+                let seated = index < 5 * players as usize;
+                if index < 25 && !seated {
+                    assert_eq!((*location, *figure), (OFF_BOARD, 0));
+                } else {
+                    assert_eq!(*figure as usize, index + 1);
+                }
+            }
+
+            assert_eq!(state.figures().count(), 5 * players as usize + 5);
+        }
+    }
+
+    #[test]
+    fn initial_state_rejects_more_than_five_players() {
+        assert!(GraphState::initial(6).is_err());
+    }
+
+    #[test]
+    fn quick_initial_state_has_three_figures_per_player() {
+        for players in 2..=5_u8 {
+            let state = GraphState::initial_variant(players, Variant::Quick).unwrap();
+            assert_eq!(state.players(), players);
+            assert_eq!(state.variant(), Variant::Quick);
+
+            for pid in 1..=players {
+                let colors = rules::figures_of(pid)
+                    .filter(|figure| state.locate(*figure).is_some())
+                    .map(|figure| (figure - 1) % 5)
+                    .collect::<Vec<u8>>();
+                assert_eq!(colors.len(), 3);
+                assert!(colors.contains(&(pid - 1)));
+            }
+        }
+    }
+}
@@ -1,8 +1,5 @@
 use super::graph::{Figure, Graph};
-use crate::api::errors::APIError;
-use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
-use serde_json::from_str;
 
 // types
 // i16 is used to be translatable to PG SMALL INT
@@ -10,6 +7,12 @@ pub type MOVE = ([i16; 6], Figure);
 pub type FIELD = [i16; 3];
 pub type LOCATION = ([i16; 3], Figure);
 
+// pseudo fields for figures that aren't on the board (see db/model GameMove)
+// OFF_BOARD: not (or no longer) in play e.g. removed gray stoppers or figures that reached their goal
+pub const OFF_BOARD: FIELD = [-1, -1, -1];
+// HAND: stoppers that were taken by a player and still need to be placed
+pub const HAND: FIELD = [-2, -2, -2];
+
 // game struct is more or less only for internal reflection as graphs don't need optional metadata
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct Game {
@@ -50,39 +53,3 @@ impl Game {
         println!("Result was {:?} and collided with {}", res.0, res.1);
     }
 }
-
-impl Move {
-    pub fn from_action(data: HashMap<String, String>) -> Result<Move, APIError> {
-        let mut action: MOVE = ([0_i16; 6], u8::MAX);
-
-        action.1 = match data.get("figure") {
-            Some(raw_id) => match raw_id.parse::<u8>() {
-                Ok(id) => id,
-                Err(_) => {
-                    return Err(APIError::ValidationError(
-                        "Value for field figure doesn't fit into u8".to_owned(),
-                    ));
-                }
-            },
-            None => {
-                return Err(APIError::ValidationError("Missing field figure".to_owned()));
-            }
-        };
-
-        action.0 = match data.get("move") {
-            Some(raw_move) => match from_str::<[i16; 6]>(raw_move) {
-                Ok(parsed_move) => parsed_move,
-                Err(_) => {
-                    return Err(APIError::ValidationError(
-                        "Value for field move doesn't fit into [i16; 6]".to_owned(),
-                    ));
-                }
-            },
-            None => {
-                return Err(APIError::ValidationError("Missing field move".to_owned()));
-            }
-        };
-
-        Ok(Move { action })
-    }
-}
@@ -0,0 +1,108 @@
+use super::bitboard::{Bits, Occupancy, BOARD};
+use super::errors::GraphErr;
+use super::graph::{Figure, GraphState};
+use super::models::{FIELD, HAND, MOVE};
+use super::rules::{assemble, figures_of, kind, pending, FigureKind};
+
+/*
+Move generation:
+    Enumerates everything the rules engine (see graph/rules) accepts for a state. The Ko-rule
+    depends on the move history and is left to the caller.
+
+    While stoppers are pending only placements (src == HAND) are generated
+*/
+
+// every field the figure could legally move to
+pub fn destinations(state: &GraphState, figure: Figure) -> Result<Vec<FIELD>, GraphErr> {
+    if !pending(state).is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let occupancy = BOARD.occupancy(state)?;
+    let blocked = match kind(figure) {
+        Some(FigureKind::Player { pid, .. }) => teammate_fields(state, pid),
+        _ => 0,
+    };
+    Ok(figure_destinations(&occupancy, blocked, state, figure))
+}
+
+// all legal moves of player {pid}
+pub fn legal_moves(state: &GraphState, pid: u8) -> Result<Vec<MOVE>, GraphErr> {
+    let occupancy = BOARD.occupancy(state)?;
+
+    let stoppers = pending(state);
+    if !stoppers.is_empty() {
+        return Ok(placements(&occupancy, &stoppers));
+    }
+
+    let blocked = teammate_fields(state, pid);
+    let mut moves: Vec<MOVE> = Vec::new();
+    for figure in figures_of(pid) {
+        let src = match state.locate(figure) {
+            Some(location) => location,
+            None => continue,
+        };
+
+        figure_destinations(&occupancy, blocked, state, figure)
+            .into_iter()
+            .for_each(|dest| moves.push(assemble(figure, src, dest)));
+    }
+
+    Ok(moves)
+}
+
+// shortcut for server side 'no legal moves' detection
+pub fn has_legal_move(state: &GraphState, pid: u8) -> Result<bool, GraphErr> {
+    Ok(!legal_moves(state, pid)?.is_empty())
+}
+
+// fields of figures player {pid} can't swap with because they belong to a teammate (see graph/variant)
+fn teammate_fields(state: &GraphState, pid: u8) -> Bits {
+    let variant = state.variant();
+
+    state
+        .figures()
+        .filter(|(_, figure)| match kind(*figure) {
+            Some(FigureKind::Player { pid: owner, .. }) => variant.teammates(pid, owner),
+            _ => false,
+        })
+        .filter_map(|(location, _)| BOARD.bit(&location))
+        .fold(0, |mask, bit| mask | bit)
+}
+
+fn figure_destinations(
+    occupancy: &Occupancy,
+    blocked: Bits,
+    state: &GraphState,
+    figure: Figure,
+) -> Vec<FIELD> {
+    // figures off the board (or in the hand) can't move
+    let src = match state
+        .locate(figure)
+        .and_then(|location| BOARD.index(&location))
+    {
+        Some(src) => src,
+        None => return Vec::new(),
+    };
+
+    // fields with multiple figures (starting corners) and blocked fields can't be entered
+    let reachable = BOARD.reachable(src, occupancy.occupied) & !occupancy.crowded & !blocked;
+
+    // masks iterate in sorted field order
+    BOARD.fields_of(reachable).collect::<Vec<FIELD>>()
+}
+
+fn placements(occupancy: &Occupancy, stoppers: &[Figure]) -> Vec<MOVE> {
+    let fields = BOARD
+        .fields_of(BOARD.all() & !occupancy.occupied)
+        .collect::<Vec<FIELD>>();
+
+    stoppers
+        .iter()
+        .flat_map(|stopper| {
+            fields
+                .iter()
+                .map(move |field| assemble(*stopper, HAND, *field))
+        })
+        .collect::<Vec<MOVE>>()
+}
@@ -0,0 +1,455 @@
+use super::board::DEFINITION;
+use super::errors::NotationError;
+use super::graph::{Figure, GraphState};
+use super::models::{FIELD, HAND, MOVE, OFF_BOARD};
+use super::rules::{self, Turn};
+use super::variant::Variant;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/*
+Position notation (Pentagame FEN):
+    Canonical string for a GraphState together with the side to move and the phase
+
+    <player 1>/.../<player n>/<gray stoppers>/<black stoppers> <turn> [<variant>]
+
+    Every group lists the fields of its five figures in figure id order separated by ','
+    (see graph/rules for the figure ids). Players that aren't seated are left out, so a game with
+    n players has n + 2 groups. The variant (see graph/variant) is left out for standard games
+
+    Fields are written in the field notation (see below). Figures that don't take part in the
+    variant are written as '-'
+
+    Turn (see graph/rules Turn):
+        w        waiting for players
+        m3       player 3 has to move
+        p3       player 3 has to place stoppers
+        f2       finished with 2 winners
+
+    The initial position of a game with two players:
+        f,g,h,i,j/f,g,h,i,j/x,x,x,x,x/a,b,c,d,e w
+
+    Every position has exactly one string (stops are only written from the lower node),
+    so parsing the output of Display always yields the same position and vice versa
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub state: GraphState,
+    pub turn: Turn,
+}
+
+impl Position {
+    pub fn new(state: GraphState, turn: Turn) -> Position {
+        Position { state, turn }
+    }
+
+    // position from the stored representation of a game (see db/model Game)
+    pub fn from_game(state: GraphState, game_state: i16) -> Result<Position, NotationError> {
+        match Turn::from_state(game_state) {
+            Some(turn) => Ok(Position { state, turn }),
+            None => Err(NotationError::InvalidTurn(game_state.to_string())),
+        }
+    }
+}
+
+// figures of every group in the order they're written
+fn groups(players: u8) -> Vec<RangeInclusive<Figure>> {
+    let mut groups = (1..=players)
+        .map(rules::figures_of)
+        .collect::<Vec<RangeInclusive<Figure>>>();
+    groups.push(rules::GRAY_STOPPERS);
+    groups.push(rules::BLACK_STOPPERS);
+    groups
+}
+
+// field of a position. Unlike moves, positions only accept the name format_field prints
+fn parse_location(raw: &str) -> Result<FIELD, NotationError> {
+    match parse_field(raw) {
+        Ok(field) if format_field(&field) == raw => Ok(field),
+        _ => Err(NotationError::InvalidField(raw.to_owned())),
+    }
+}
+
+impl fmt::Display for Turn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Turn::Waiting => write!(f, "w"),
+            Turn::Move(pid) => write!(f, "m{}", pid),
+            Turn::Placement(pid) => write!(f, "p{}", pid),
+            Turn::Finished(winners) => write!(f, "f{}", winners),
+        }
+    }
+}
+
+impl FromStr for Turn {
+    type Err = NotationError;
+
+    fn from_str(raw: &str) -> Result<Turn, NotationError> {
+        let invalid = || NotationError::InvalidTurn(raw.to_owned());
+
+        if raw == "w" {
+            return Ok(Turn::Waiting);
+        } else if raw.len() < 2 || !raw.is_char_boundary(1) {
+            return Err(invalid());
+        }
+
+        let (phase, value) = raw.split_at(1);
+        let value = value.parse::<u8>().map_err(|_| invalid())?;
+
+        // reuse the range checks of games.state
+        let turn = match phase {
+            "m" if value > 0 => Turn::from_state(i16::from(value)),
+            "p" if value > 0 => Turn::from_state(5 + i16::from(value)),
+            "f" => Turn::from_state(10 + i16::from(value)),
+            _ => None,
+        };
+
+        match turn {
+            Some(turn) if turn.to_string() == raw => Ok(turn),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, group) in groups(self.state.players()).into_iter().enumerate() {
+            if index > 0 {
+                write!(f, "/")?;
+            }
+
+            for (offset, figure) in group.enumerate() {
+                if offset > 0 {
+                    write!(f, ",")?;
+                }
+                match self.state.locate(figure) {
+                    Some(location) => write!(f, "{}", format_field(&location))?,
+                    None => write!(f, "-")?,
+                };
+            }
+        }
+
+        write!(f, " {}", self.turn)?;
+        match self.state.variant() {
+            Variant::Standard => Ok(()),
+            variant => write!(f, " {}", variant),
+        }
+    }
+}
+
+impl FromStr for Position {
+    type Err = NotationError;
+
+    fn from_str(raw: &str) -> Result<Position, NotationError> {
+        let (board, turn, variant) =
+            match raw.split(' ').collect::<Vec<&str>>().as_slice() {
+                [board, turn] => (*board, turn.parse::<Turn>()?, Variant::Standard),
+                // standard games are written without their variant
+                [board, turn, variant] if *variant != "standard" => (
+                    *board,
+                    turn.parse::<Turn>()?,
+                    variant
+                        .parse::<Variant>()
+                        .map_err(NotationError::Malformed)?,
+                ),
+                _ => return Err(NotationError::Malformed(
+                    "Expected the figures, the turn and the variant separated by a single space"
+                        .to_owned(),
+                )),
+            };
+
+        let groups = board.split('/').collect::<Vec<&str>>();
+        if groups.len() < 2 || groups.len() > 7 {
+            return Err(NotationError::InvalidGroupCount(groups.len()));
+        }
+
+        let players = groups.len() as u8 - 2;
+        let mut state = GraphState::initial_variant(players, variant)
+            .map_err(|_| NotationError::InvalidGroupCount(groups.len()))?;
+
+        for (index, (group, figures)) in groups.iter().zip(self::groups(players)).enumerate() {
+            let locations = group
+                .split(',')
+                .map(|location| match location {
+                    "-" => Ok(None),
+                    _ => parse_location(location).map(Some),
+                })
+                .collect::<Result<Vec<Option<FIELD>>, NotationError>>()?;
+
+            if locations.len() != figures.clone().count() {
+                return Err(NotationError::InvalidGroup(index + 1));
+            }
+
+            for (figure, location) in figures.zip(locations) {
+                let location = match (location, variant.plays(figure)) {
+                    (Some(location), true) => location,
+                    (None, false) => continue,
+                    _ => return Err(NotationError::InvalidGroup(index + 1)),
+                };
+
+                // only stoppers can be taken into the hand of a player
+                if let (HAND, Some(rules::FigureKind::Player { .. })) =
+                    (location, rules::kind(figure))
+                {
+                    return Err(NotationError::InvalidGroup(index + 1));
+                }
+                state.relocate(figure, location);
+            }
+        }
+
+        validate_occupancy(&state)?;
+
+        match turn {
+            Turn::Move(pid) | Turn::Placement(pid) if pid > players => {
+                Err(NotationError::InvalidTurn(turn.to_string()))
+            }
+            _ => Ok(Position { state, turn }),
+        }
+    }
+}
+
+// only the starting corners may hold more than one figure
+fn validate_occupancy(state: &GraphState) -> Result<(), NotationError> {
+    let mut locations = state
+        .figures()
+        .map(|(location, _)| location)
+        .collect::<Vec<FIELD>>();
+    locations.sort();
+
+    for pair in locations.windows(2) {
+        let location = pair[0];
+        let corner = location[1] == 0 && (5..=9).contains(&location[0]);
+        if location == pair[1] && !corner {
+            return Err(NotationError::CrowdedField(format_field(&location)));
+        }
+    }
+
+    Ok(())
+}
+
+/*
+Field notation:
+    Algebraic names for fields used by positions and moves (records, errors and the websocket)
+
+    a - e    junctions 0 - 4
+    f - j    corners 5 - 9 (the starting corner of color c is the (c + 1)th of them)
+    af3      third stop on the line from a to f. Moves may name it from the other end as well
+             (fa4 on a line with 6 stops), but it's always printed from the lower node like in the
+             database (see db/model GameMove)
+    @        HAND (stoppers waiting to be placed)
+    x        OFF_BOARD
+
+Move notation:
+    <figure>:<src>-<dest> e.g. 1:f-a (figure 1 moves from corner 5 to junction 0) or
+    31:@-ag2 (black stopper 31 is placed on the second stop between a and g)
+    Passes and resignations (see graph/rules PASS) are written as pass and resign
+*/
+const NODE_NAMES: &str = "abcdefghij";
+
+fn node_name(node: i16) -> Option<char> {
+    NODE_NAMES.chars().nth(usize::try_from(node).ok()?)
+}
+
+fn parse_node(name: char) -> Option<i16> {
+    NODE_NAMES.find(name).map(|node| node as i16)
+}
+
+pub fn format_field(field: &FIELD) -> String {
+    let name = match *field {
+        OFF_BOARD => Some("x".to_owned()),
+        HAND => Some("@".to_owned()),
+        [node, 0, 0] => node_name(node).map(String::from),
+        [base, count, target] => node_name(base)
+            .and_then(|base| Some(format!("{}{}{}", base, node_name(target)?, count))),
+    };
+
+    // fall back to the raw coordinates for fields that aren't part of the board
+    name.unwrap_or_else(|| format!("{:?}", field))
+}
+
+pub fn parse_field(raw: &str) -> Result<FIELD, NotationError> {
+    let invalid = || NotationError::InvalidField(raw.to_owned());
+    let mut chars = raw.chars();
+
+    let field = match raw {
+        "x" => OFF_BOARD,
+        "@" => HAND,
+        _ => {
+            let first = chars.next().and_then(parse_node).ok_or_else(invalid)?;
+            let second = match chars.next() {
+                Some(name) => parse_node(name).ok_or_else(invalid)?,
+                None => return Ok([first, 0, 0]),
+            };
+            let digits = chars.as_str();
+            if digits.is_empty()
+                || digits.starts_with('0')
+                || !digits.chars().all(|digit| digit.is_ascii_digit())
+            {
+                return Err(invalid());
+            }
+            let count = digits.parse::<i16>().map_err(|_| invalid())?;
+
+            let (base, target, stops) = DEFINITION.line(first, second).ok_or_else(invalid)?;
+            if !(1..=stops).contains(&count) {
+                return Err(invalid());
+            }
+
+            // stops are counted from the node that was written first
+            if base == first {
+                [base, count, target]
+            } else {
+                [base, stops + 1 - count, target]
+            }
+        }
+    };
+
+    Ok(field)
+}
+
+pub fn format_move(action: &MOVE) -> String {
+    match *action {
+        rules::PASS => "pass".to_owned(),
+        rules::RESIGN => "resign".to_owned(),
+        _ => {
+            let (figure, src, dest) = rules::split(action);
+            format!("{}:{}-{}", figure, format_field(&src), format_field(&dest))
+        }
+    }
+}
+
+pub fn parse_move(raw: &str) -> Result<MOVE, NotationError> {
+    let invalid = || NotationError::InvalidMove(raw.to_owned());
+
+    match raw {
+        "pass" => return Ok(rules::PASS),
+        "resign" => return Ok(rules::RESIGN),
+        _ => (),
+    };
+
+    let mut parts = raw.splitn(2, ':');
+    let figure = parts
+        .next()
+        .and_then(|figure| match figure.parse::<Figure>() {
+            // no leading zeros or signs
+            Ok(id) if id.to_string() == figure => Some(id),
+            _ => None,
+        })
+        .ok_or_else(invalid)?;
+
+    let mut fields = parts.next().ok_or_else(invalid)?.splitn(2, '-');
+    let src = parse_field(fields.next().ok_or_else(invalid)?)?;
+    let dest = parse_field(fields.next().ok_or_else(invalid)?)?;
+
+    Ok(rules::assemble(figure, src, dest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::graph::GRAPH;
+    use crate::graph::rules::{apply_move, assemble};
+
+    #[test]
+    fn initial_position_round_trip() {
+        let raw = "f,g,h,i,j/f,g,h,i,j/x,x,x,x,x/a,b,c,d,e m1";
+        let position = raw.parse::<Position>().expect("valid position");
+
+        assert_eq!(
+            position,
+            Position::new(GraphState::initial(2).unwrap(), Turn::Move(1))
+        );
+        assert_eq!(position.to_string(), raw);
+    }
+
+    #[test]
+    fn position_round_trip_after_moves() {
+        let initial = GraphState::initial(3).unwrap();
+        let outcome = apply_move(&initial, 1, &assemble(1, [5, 0, 0], [0, 0, 0])).unwrap();
+        let position = Position::new(outcome.state, Turn::Placement(1));
+
+        let raw = position.to_string();
+        assert_eq!(raw, "a,g,h,i,j/f,g,h,i,j/f,g,h,i,j/x,x,x,x,x/@,b,c,d,e p1");
+        assert_eq!(raw.parse::<Position>(), Ok(position));
+    }
+
+    #[test]
+    fn rejects_non_canonical_positions() {
+        for raw in &[
+            "ba3,g,h,i,j/f,g,h,i,j/x,x,x,x,x/a,b,c,d,e w",
+            "f,g,h,i,j/f,g,h,i,j/x,x,x,x,x/a,b,c,d,e  w",
+            "f,g,h,i/f,g,h,i,j/x,x,x,x,x/a,b,c,d,e w",
+            "@,g,h,i,j/f,g,h,i,j/x,x,x,x,x/a,b,c,d,e w",
+            "f,g,h,i,j/f,g,h,i,j/x,x,x,x,x/a,b,c,d,e m3",
+            "f,g,h,i,j/f,g,h,i,j/x,x,x,x,x/a,b,c,d,e m01",
+            "f,g,h,i,j/f,g,h,i,j/x,x,x,x,x/a,a,c,d,e w",
+            "f,g,h,i,j/f,g,h,i,j/x,x,x,x,x/a,b,c,d,ab9 w",
+            "f,g,h,i,j/f,g,h,i,j/x,x,x,x,x/a,b,c,d,e w standard",
+            "f,g,h,i,j/f,g,h,i,j/x,x,x,x,x/a,b,c,d,e w quick",
+            "f,g,h,-,-/-,g,h,i,j/x,x,x,x,x/a,b,c,d,e w",
+        ] {
+            assert!(raw.parse::<Position>().is_err(), "{} was accepted", raw);
+        }
+    }
+
+    #[test]
+    fn variant_round_trip() {
+        let raw = "f,g,h,-,-/-,g,h,i,-/x,x,x,x,x/a,b,c,d,e m1 quick";
+        let position = raw.parse::<Position>().expect("valid position");
+
+        assert_eq!(
+            position,
+            Position::new(
+                GraphState::initial_variant(2, Variant::Quick).unwrap(),
+                Turn::Move(1)
+            )
+        );
+        assert_eq!(position.to_string(), raw);
+
+        let teams = Position::new(
+            GraphState::initial_variant(4, Variant::Teams).unwrap(),
+            Turn::Waiting,
+        );
+        assert_eq!(teams.to_string().parse::<Position>(), Ok(teams));
+        assert!(teams.to_string().ends_with(" w teams"));
+    }
+
+    #[test]
+    fn fields_round_trip() {
+        let mut fields = GRAPH.vertices.keys().copied().collect::<Vec<FIELD>>();
+        fields.push(OFF_BOARD);
+        fields.push(HAND);
+
+        for field in fields {
+            let name = format_field(&field);
+            let parsed = parse_field(&name).expect("valid field");
+            assert_eq!(parsed, field);
+            assert_eq!(format_field(&parsed), name);
+
+            assert_eq!(parse_location(&name), Ok(field));
+        }
+    }
+
+    #[test]
+    fn moves_round_trip() {
+        for raw in &[
+            "1:f-a", "31:@-ag2", "26:af3-x", "12:ag1-b", "pass", "resign",
+        ] {
+            assert_eq!(format_move(&parse_move(raw).unwrap()), *raw);
+        }
+    }
+
+    #[test]
+    fn rejects_leading_zeros() {
+        for raw in &["af03", "af+3", "af0"] {
+            assert!(parse_field(raw).is_err(), "{} was accepted", raw);
+        }
+        // stops written from the higher node are only accepted in moves
+        assert_eq!(parse_field("ba3"), parse_field("ab1"));
+        assert!(parse_location("ba3").is_err());
+        for raw in &["01:f-a", "+1:f-a"] {
+            assert!(parse_move(raw).is_err(), "{} was accepted", raw);
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use super::errors::GraphErr;
+use super::models::{HAND, MOVE};
+use super::movegen::legal_moves;
+use super::notation::Position;
+use super::rules::{self, Turn};
+
+/*
+Perft:
+    Counts the leaf positions of the game tree up to a depth. The counts of known positions are
+    pinned in the tests below, so changes to the move generation (see graph/movegen) or the rules
+    engine that alter the game tree show up as regressions
+
+    Placements are plies of their own. The Ko-rule and repetitions depend on the move history and
+    aren't applied. Finished positions are leaves with no positions below them
+*/
+
+// every position reachable from {position} with a single move or placement
+fn children(position: &Position) -> Result<Vec<(MOVE, Position)>, GraphErr> {
+    let pid = match position.turn {
+        Turn::Move(pid) | Turn::Placement(pid) => pid,
+        _ => return Ok(Vec::new()),
+    };
+    let players = position.state.players();
+
+    let mut children: Vec<(MOVE, Position)> = Vec::new();
+    for action in legal_moves(&position.state, pid)? {
+        let state = if rules::split(&action).1 == HAND {
+            rules::apply_placement(&position.state, &action)
+        } else {
+            rules::apply_move(&position.state, pid, &action).map(|outcome| outcome.state)
+        }
+        .map_err(|why| GraphErr::CannotConstructState(why.to_string()))?;
+
+        let turn = rules::turn_after(&state, pid, players);
+        children.push((action, Position::new(state, turn)));
+    }
+
+    Ok(children)
+}
+
+// amount of positions {depth} plies below {position}
+pub fn perft(position: &Position, depth: u8) -> Result<u64, GraphErr> {
+    match depth {
+        0 => Ok(1),
+        // the leaves don't have to be visited
+        1 => Ok(children(position)?.len() as u64),
+        _ => {
+            let mut nodes = 0;
+            for (_, child) in children(position)? {
+                nodes += perft(&child, depth - 1)?;
+            }
+            Ok(nodes)
+        }
+    }
+}
+
+// perft split up by the first move (useful for finding the move that changed a count)
+pub fn divide(position: &Position, depth: u8) -> Result<Vec<(MOVE, u64)>, GraphErr> {
+    if depth == 0 {
+        return Ok(Vec::new());
+    }
+
+    children(position)?
+        .iter()
+        .map(|(action, child)| Ok((*action, perft(child, depth - 1)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::graph::{GraphState, GRAPH};
+    use crate::graph::models::FIELD;
+
+    fn initial(players: u8) -> Position {
+        Position::new(GraphState::initial(players).unwrap(), Turn::Move(1))
+    }
+
+    // move generation based on the breadth first search of Graph instead of the bitboard
+    fn reference_perft(position: &Position, depth: u8) -> u64 {
+        let players = position.state.players();
+        let pid = match position.turn {
+            Turn::Move(pid) if depth > 0 => pid,
+            // stoppers may be placed on every empty field
+            Turn::Placement(pid) if depth > 0 => {
+                let mut nodes = 0;
+                for stopper in rules::pending(&position.state) {
+                    for dest in GRAPH.vertices.keys() {
+                        if position.state.occupants(dest).is_empty() {
+                            let state = rules::apply_placement(
+                                &position.state,
+                                &rules::assemble(stopper, HAND, *dest),
+                            )
+                            .unwrap();
+                            nodes += reference_perft(
+                                &Position::new(state, rules::next_turn(&state, pid, players)),
+                                depth - 1,
+                            );
+                        }
+                    }
+                }
+                return nodes;
+            }
+            _ => return 1,
+        };
+
+        let mut graph = GRAPH.clone();
+        graph.load_state(position.state).unwrap();
+
+        let mut nodes = 0;
+        for figure in rules::figures_of(pid) {
+            let src = match position.state.locate(figure) {
+                Some(src) if GRAPH.vertices.contains_key(&src) => src,
+                _ => continue,
+            };
+
+            for dest in GRAPH.vertices.keys().copied().collect::<Vec<FIELD>>() {
+                let crowded = position.state.occupants(&dest).len() > 1;
+                if dest == src || crowded || !graph.validate(&src, &dest).unwrap().0 {
+                    continue;
+                }
+
+                let state =
+                    rules::apply_move(&position.state, pid, &rules::assemble(figure, src, dest))
+                        .unwrap()
+                        .state;
+                nodes += reference_perft(
+                    &Position::new(state, rules::next_turn(&state, pid, players)),
+                    depth - 1,
+                );
+            }
+        }
+
+        nodes
+    }
+
+    #[test]
+    fn initial_positions() {
+        // (players, perft 1, perft 2)
+        let known: [(u8, u64, u64); 4] = [
+            (2, 100, 9_780),
+            (3, 100, 9_630),
+            (4, 100, 9_630),
+            (5, 100, 9_630),
+        ];
+
+        for (players, first, second) in known.iter() {
+            let position = initial(*players);
+            assert_eq!(perft(&position, 1).unwrap(), *first);
+            assert_eq!(perft(&position, 2).unwrap(), *second);
+        }
+    }
+
+    #[test]
+    fn divide_adds_up() {
+        let position = initial(3);
+        let total = divide(&position, 2)
+            .unwrap()
+            .iter()
+            .map(|(_, nodes)| nodes)
+            .sum::<u64>();
+
+        assert_eq!(total, perft(&position, 2).unwrap());
+        assert!(divide(&position, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn agrees_with_graph_search() {
+        let position = initial(2);
+        assert_eq!(perft(&position, 2).unwrap(), reference_perft(&position, 2));
+    }
+
+    #[test]
+    fn finished_positions_are_leaves() {
+        let position = Position::new(GraphState::initial(2).unwrap(), Turn::Finished(1));
+        assert_eq!(perft(&position, 0).unwrap(), 1);
+        assert_eq!(perft(&position, 3).unwrap(), 0);
+    }
+}
@@ -0,0 +1,512 @@
+use super::errors::{GraphErr, RecordError, RuleViolation};
+use super::graph::{Figure, GraphState};
+use super::models::{HAND, MOVE};
+use super::notation::{format_move, parse_move, Position};
+use super::rules::{self, FigureKind, Turn};
+use super::search::last_moves;
+use super::variant::Variant;
+use super::zobrist;
+use diesel::PgConnection;
+use hashbrown::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/*
+Game record (similar to PGN):
+    Header tags followed by the move list and the result
+
+    [Game "Friday evening"]
+    [Date "2020.11.06"]
+    [Player1 "alice"]
+    [Player2 "bob"]
+    [Result "1-2"]
+
+    1. 1:f-a 2. 31:@-ag3 3. 7:g-gh2 ... 1-2
+
+    Tags:
+        Game        name of the game
+        Date        date of the export (games don't store when they were played)
+        Player{n}   username of the player in seat n (see db/model UserGame). Required for 2-5 seats
+        Result      rank of every player in seat order separated by '-' or '*' for running games
+        Variant     rule set of the game (see graph/variant). Standard games leave it out
+
+    Moves are written in the move notation (see graph/notation). Score moves (figure 42) aren't
+    part of the record because the result is derived from the moves. Passes and resignations of
+    computer and absent players are part of it (see graph/rules PASS). The move list ends with the result
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameRecord {
+    // tags in the order they're written
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<MOVE>,
+}
+
+// result of a running game
+pub const UNFINISHED: &str = "*";
+// lines of the move list are wrapped after this many characters
+const LINE_WIDTH: usize = 80;
+
+impl GameRecord {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // usernames of the seated players in seat order
+    pub fn players(&self) -> Vec<&str> {
+        let mut players: Vec<&str> = Vec::with_capacity(5);
+        for seat in 1..=5 {
+            match self.tag(&format!("Player{}", seat)) {
+                Some(player) => players.push(player),
+                None => break,
+            }
+        }
+        players
+    }
+
+    // Variant tag of the record (standard if it's missing)
+    pub fn variant(&self) -> Result<Variant, RecordError> {
+        match self.tag("Variant") {
+            Some(variant) => variant.parse::<Variant>().map_err(RecordError::Malformed),
+            None => Ok(Variant::Standard),
+        }
+    }
+
+    // Result tag of the record (UNFINISHED if it's missing)
+    pub fn result(&self) -> &str {
+        self.tag("Result").unwrap_or(UNFINISHED)
+    }
+
+    /*
+    Replays the moves through the rules engine (including turn order, the Ko-rule and threefold
+    repetition) and returns the final position. The Result tag has to match the ranking the moves lead to
+    */
+    pub fn replay(&self) -> Result<Position, RecordError> {
+        let (position, _, result) = self.run(self.moves.len())?;
+
+        if result != self.result() {
+            return Err(RecordError::ResultMismatch(
+                self.result().to_owned(),
+                result,
+            ));
+        }
+
+        Ok(position)
+    }
+
+    // position after the first {ply} moves and the hashes of all positions up to it (see replay)
+    pub fn replay_to(&self, ply: usize) -> Result<(Position, Vec<u64>), RecordError> {
+        if ply > self.moves.len() {
+            return Err(RecordError::Malformed(format!(
+                "The record only has {} moves",
+                self.moves.len()
+            )));
+        }
+
+        let (position, positions, _) = self.run(ply)?;
+        Ok((position, positions))
+    }
+
+    // position after the first {ply} moves with the last moves and the history search::analyse needs
+    pub fn analysis_input(
+        &self,
+        ply: usize,
+    ) -> Result<(Position, Vec<MOVE>, Vec<u64>), RecordError> {
+        let (position, history) = self.replay_to(ply)?;
+        let last = match position.turn {
+            Turn::Move(pid) | Turn::Placement(pid) => last_moves(&self.moves[..ply], pid),
+            _ => Vec::new(),
+        };
+
+        Ok((position, last, history))
+    }
+
+    // replay the first {ply} moves. Returns the position, its history and the result
+    fn run(&self, ply: usize) -> Result<(Position, Vec<u64>, String), RecordError> {
+        let players = self.players().len() as u8;
+        let variant = self.variant()?;
+        if !variant.players().contains(&players) {
+            return Err(RecordError::Malformed(format!(
+                "A {} game requires {} but the record has {}",
+                variant,
+                variant.players_text(),
+                players
+            )));
+        }
+
+        let mut state = GraphState::initial_variant(players, variant)
+            .map_err(|why| RecordError::Malformed(why.to_string()))?;
+        let mut turn = Turn::Move(1);
+        let mut result = UNFINISHED.to_owned();
+        // last move of every figure (see rules::is_reversal)
+        let mut history: HashMap<Figure, MOVE> = HashMap::with_capacity(25);
+        // hashes of all positions (see zobrist::is_repetition)
+        let mut positions: Vec<u64> = vec![zobrist::hash(&Position::new(state, turn))];
+
+        for (index, action) in self.moves.iter().take(ply).enumerate() {
+            let illegal = |reason| RecordError::IllegalMove(index + 1, format_move(action), reason);
+            let (_, src, _) = rules::split(action);
+
+            let pid = match turn {
+                Turn::Move(pid) | Turn::Placement(pid) => pid,
+                _ => return Err(illegal(RuleViolation::NotRunning)),
+            };
+            let before = Position::new(state, turn);
+
+            turn = match (*action, turn) {
+                // the turn goes to the next player that can move
+                (rules::PASS, Turn::Move(_)) => rules::pass_turn(&state, pid, players)
+                    .ok_or_else(|| illegal(RuleViolation::CannotPass))?,
+                // the game ends with the current points (players can't pass a placement)
+                (rules::RESIGN, _) => {
+                    let ranking = rules::standings(&state, players);
+                    result = format_result(&ranking);
+                    Turn::Finished(rules::winners(&ranking))
+                }
+                (rules::PASS, _) => return Err(illegal(RuleViolation::PlacementPending)),
+                _ => {
+                    state = play(&state, turn, action, &mut history).map_err(illegal)?;
+
+                    match rules::ranking(&state, players) {
+                        Some(ranking) => {
+                            result = format_result(&ranking);
+                            Turn::Finished(rules::winners(&ranking))
+                        }
+                        None => rules::next_turn(&state, pid, players),
+                    }
+                }
+            };
+
+            let hash = zobrist::update(
+                positions[positions.len() - 1],
+                &before,
+                &Position::new(state, turn),
+            );
+            // resignations end the game -> they can't repeat a position
+            if src != HAND && *action != rules::RESIGN && zobrist::is_repetition(&positions, hash) {
+                return Err(illegal(RuleViolation::ThreefoldRepetition));
+            }
+            positions.push(hash);
+        }
+
+        Ok((Position::new(state, turn), positions, result))
+    }
+
+    // record of a running or finished game
+    pub fn from_db(conn: &PgConnection, gid: i32) -> Result<GameRecord, GraphErr> {
+        use crate::db::actions::{get_game, get_game_seats};
+
+        let (game, users) = get_game(conn, gid)?;
+        match Turn::from_state(game.state) {
+            Some(Turn::Waiting) | None => {
+                return Err(GraphErr::CannotConstructState(format!(
+                    "Game {} hasn't started yet",
+                    gid
+                )));
+            }
+            _ => (),
+        };
+
+        let mut tags = vec![
+            ("Game".to_owned(), game.name),
+            (
+                "Date".to_owned(),
+                chrono::Utc::now().format("%Y.%m.%d").to_string(),
+            ),
+        ];
+
+        let seats = get_game_seats(conn, gid)?;
+        for (index, seat) in seats.iter().enumerate() {
+            let username = match users.iter().find(|(uid, _)| uid == seat) {
+                Some((_, username)) => username.clone(),
+                None => seat.to_string(),
+            };
+            tags.push((format!("Player{}", index + 1), username));
+        }
+
+        // the final ranking is stored in the score moves (see db/model GameMove)
+        let (scores, moves): (Vec<MOVE>, Vec<MOVE>) = GraphState::load_moves(conn, gid)?
+            .into_iter()
+            .map(|(_, action)| action)
+            .partition(|(_, figure)| *figure == rules::SCORE_FIGURE);
+
+        let result = if scores.is_empty() {
+            UNFINISHED.to_owned()
+        } else {
+            format_result(
+                &scores
+                    .iter()
+                    .map(|(score, _)| (score[0] as u8, score[1] as u8, score[2] as u8))
+                    .collect::<Vec<(u8, u8, u8)>>(),
+            )
+        };
+        tags.push(("Result".to_owned(), result));
+
+        match Variant::from_id(game.variant) {
+            Some(Variant::Standard) => (),
+            Some(variant) => tags.push(("Variant".to_owned(), variant.to_string())),
+            None => {
+                return Err(GraphErr::CannotConstructState(format!(
+                    "Unknown variant {}",
+                    game.variant
+                )))
+            }
+        };
+
+        Ok(GameRecord { tags, moves })
+    }
+}
+
+// state after a move or placement of the player whose turn it is (see GameRecord::run)
+fn play(
+    state: &GraphState,
+    turn: Turn,
+    action: &MOVE,
+    history: &mut HashMap<Figure, MOVE>,
+) -> Result<GraphState, RuleViolation> {
+    let (figure, src, _) = rules::split(action);
+
+    match (turn, src == HAND) {
+        (Turn::Placement(_), true) => rules::apply_placement(state, action),
+        (Turn::Placement(_), false) => Err(RuleViolation::PlacementPending),
+        (Turn::Move(pid), false) => {
+            match rules::kind(figure) {
+                Some(FigureKind::Player { pid: owner, .. }) if owner != pid => {
+                    return Err(RuleViolation::OutOfTurn(pid));
+                }
+                _ => (),
+            };
+
+            if let Some(last) = history.get(&figure) {
+                if rules::is_reversal(last, action) {
+                    return Err(RuleViolation::Repetition);
+                }
+            }
+            history.insert(figure, *action);
+
+            Ok(rules::apply_move(state, pid, action)?.state)
+        }
+        (Turn::Move(_), true) => Err(RuleViolation::NotPending(figure)),
+        _ => Err(RuleViolation::NotRunning),
+    }
+}
+
+// ranks of (pid, points, rank) in seat order
+fn format_result(ranking: &[(u8, u8, u8)]) -> String {
+    let mut ranks = ranking.to_vec();
+    ranks.sort();
+
+    ranks
+        .iter()
+        .map(|(_, _, rank)| rank.to_string())
+        .collect::<Vec<String>>()
+        .join("-")
+}
+
+// escape quotes and backslashes of tag values
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (tag, value) in &self.tags {
+            writeln!(f, "[{} \"{}\"]", tag, escape(value))?;
+        }
+        writeln!(f)?;
+
+        let tokens = self
+            .moves
+            .iter()
+            .enumerate()
+            .map(|(index, action)| format!("{}. {}", index + 1, format_move(action)))
+            .chain(std::iter::once(self.result().to_owned()));
+
+        let mut width = 0;
+        for token in tokens {
+            if width > 0 && width + token.len() + 1 > LINE_WIDTH {
+                writeln!(f)?;
+                width = 0;
+            } else if width > 0 {
+                write!(f, " ")?;
+                width += 1;
+            }
+
+            write!(f, "{}", token)?;
+            width += token.len();
+        }
+
+        writeln!(f)
+    }
+}
+
+// parse [Tag "value"]
+fn parse_tag(line: &str) -> Result<(String, String), RecordError> {
+    let malformed = || RecordError::Malformed(format!("Invalid tag {}", line));
+
+    let inner = line
+        .strip_prefix('[')
+        .and_then(|line| line.strip_suffix(']'))
+        .ok_or_else(malformed)?;
+    let mut parts = inner.splitn(2, ' ');
+    let name = parts
+        .next()
+        .filter(|name| !name.is_empty())
+        .ok_or_else(malformed)?;
+    let quoted = parts
+        .next()
+        .and_then(|value| value.strip_prefix('"'))
+        .and_then(|value| value.strip_suffix('"'))
+        .ok_or_else(malformed)?;
+
+    let mut value = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(character) = chars.next() {
+        match character {
+            '\\' => value.push(chars.next().ok_or_else(malformed)?),
+            '"' => return Err(malformed()),
+            _ => value.push(character),
+        }
+    }
+
+    Ok((name.to_owned(), value))
+}
+
+impl FromStr for GameRecord {
+    type Err = RecordError;
+
+    fn from_str(raw: &str) -> Result<GameRecord, RecordError> {
+        let mut tags: Vec<(String, String)> = Vec::new();
+        let mut movetext = String::new();
+
+        for line in raw.lines().map(str::trim) {
+            if line.starts_with('[') && movetext.is_empty() {
+                tags.push(parse_tag(line)?);
+            } else {
+                movetext.push_str(line);
+                movetext.push(' ');
+            }
+        }
+
+        let mut tokens = movetext.split_whitespace().collect::<Vec<&str>>();
+
+        // the move list is terminated by the result
+        let result = match tokens.pop() {
+            Some(result) => result,
+            None => {
+                return Err(RecordError::Malformed(
+                    "The move list has to end with the result".to_owned(),
+                ))
+            }
+        };
+
+        match tags.iter().find(|(tag, _)| tag == "Result") {
+            Some((_, value)) if value != result => {
+                return Err(RecordError::Malformed(format!(
+                    "The move list ends with {} but the Result tag is {}",
+                    result, value
+                )));
+            }
+            Some(_) => (),
+            None => tags.push(("Result".to_owned(), result.to_owned())),
+        };
+
+        let mut moves: Vec<MOVE> = Vec::with_capacity(tokens.len() / 2);
+        for token in tokens {
+            // move numbers are only there for readers
+            if token == format!("{}.", moves.len() + 1) {
+                continue;
+            }
+
+            match parse_move(token) {
+                Ok(action) => moves.push(action),
+                Err(_) => return Err(RecordError::InvalidMove(moves.len() + 1, token.to_owned())),
+            }
+        }
+
+        Ok(GameRecord { tags, moves })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::movegen::legal_moves;
+
+    // first legal move of player {pid} that doesn't take a stopper
+    fn quiet_move(state: &GraphState, pid: u8) -> MOVE {
+        legal_moves(state, pid)
+            .unwrap()
+            .into_iter()
+            .find(|action| {
+                let after = rules::apply_move(state, pid, action).unwrap().state;
+                rules::next_turn(&after, pid, 2) != Turn::Placement(pid)
+            })
+            .expect("a move without placement")
+    }
+
+    #[test]
+    fn bot_games_with_passes_round_trip() {
+        let mut state = GraphState::initial_variant(2, Variant::Standard).unwrap();
+        let first = quiet_move(&state, 1);
+        state = rules::apply_move(&state, 1, &first).unwrap().state;
+        let second = quiet_move(&state, 1);
+
+        let record = GameRecord {
+            tags: vec![
+                ("Player1".to_owned(), "alice".to_owned()),
+                (
+                    "Player2".to_owned(),
+                    format!("{}1.2", crate::db::model::BOT_PREFIX),
+                ),
+                ("Result".to_owned(), "1-1".to_owned()),
+            ],
+            moves: vec![first, rules::PASS, second, rules::RESIGN],
+        };
+
+        let text = record.to_string();
+        assert!(text.contains("2. pass") && text.contains("4. resign"));
+        assert_eq!(text.parse::<GameRecord>(), Ok(record.clone()));
+        assert_eq!(record.replay().unwrap().turn, Turn::Finished(2));
+
+        // passing hands the turn back to the human
+        let (position, _) = record.replay_to(2).unwrap();
+        assert_eq!(position.turn, Turn::Move(1));
+    }
+
+    #[test]
+    fn resignations_end_placements() {
+        let state = GraphState::initial_variant(2, Variant::Standard).unwrap();
+        let take = legal_moves(&state, 1)
+            .unwrap()
+            .into_iter()
+            .find(|action| {
+                let after = rules::apply_move(&state, 1, action).unwrap().state;
+                rules::next_turn(&after, 1, 2) == Turn::Placement(1)
+            })
+            .expect("a move taking a stopper");
+
+        let mut record = GameRecord {
+            tags: vec![
+                ("Player1".to_owned(), "alice".to_owned()),
+                ("Player2".to_owned(), "bob".to_owned()),
+                ("Result".to_owned(), "1-1".to_owned()),
+            ],
+            moves: vec![take, rules::RESIGN],
+        };
+        assert_eq!(record.replay().unwrap().turn, Turn::Finished(2));
+
+        // stoppers have to be placed before the turn can be passed
+        record.moves[1] = rules::PASS;
+        assert!(matches!(
+            record.replay(),
+            Err(RecordError::IllegalMove(
+                2,
+                _,
+                RuleViolation::PlacementPending
+            ))
+        ));
+    }
+}
@@ -0,0 +1,332 @@
+use super::board::{BoardDefinition, DEFINITION};
+use super::graph::{Figure, GraphState};
+use super::models::{FIELD, HAND, MOVE};
+use super::rules::{self, FigureKind};
+use hashbrown::HashMap;
+use std::fmt::Write;
+
+/*
+Board rendering:
+    Renders a GraphState to a standalone SVG of the pentagram (see render). Used for the board
+    images of games (/games/{id}/board.svg) so positions can be shown without static/js/pentagame.js
+
+    The corners lie on the outer circle and the junctions on the inner pentagon of a regular
+    pentagram. Lines between neighbouring corners follow the outer circle, every other line is
+    straight and its stops are spread evenly over it. This works for every board definition
+    (see graph/board) because they all share the nodes 0-9
+
+    Figures that reached their goal (OFF_BOARD) aren't drawn, stoppers that still have to be placed
+    (HAND) are drawn below the board
+*/
+
+// width of the image. The row of pending stoppers is added below the board
+pub const SIZE: f64 = 500.0;
+const HAND_HEIGHT: f64 = 40.0;
+const CENTER: f64 = SIZE / 2.0;
+// radius of the circle the corners lie on
+const RADIUS: f64 = 200.0;
+// radius of the inner pentagon relative to RADIUS (cos 72° / cos 36°)
+const INNER: f64 = 0.381_966;
+
+const BACKGROUND: &str = "#28292b";
+const FOREGROUND: &str = "#d3d3d3";
+const HIGHLIGHT: &str = "#f0ad4e";
+// colors of the figures and corners in color order (same as static/js/core.js)
+const COLORS: [&str; 5] = ["blue", "white", "green", "yellow", "red"];
+const GRAY_STOPPER: &str = "#808080";
+const BLACK_STOPPER: &str = "#000000";
+
+const CORNER_RADIUS: f64 = 13.0;
+const JUNCTION_RADIUS: f64 = 10.0;
+const STOP_RADIUS: f64 = 5.0;
+const FIGURE_RADIUS: f64 = 7.0;
+
+lazy_static! {
+    // coordinates of every field of the board
+    static ref LAYOUT: HashMap<FIELD, (f64, f64)> = layout(&DEFINITION);
+}
+
+fn polar(radius: f64, degrees: f64) -> (f64, f64) {
+    let angle = degrees.to_radians();
+    (CENTER + radius * angle.cos(), CENTER + radius * angle.sin())
+}
+
+// angle of a corner or junction (corner 5 is at the top, junction n lies between corner 5 + n and 6 + n)
+fn angle(node: i16) -> f64 {
+    match node {
+        5..=9 => -90.0 + 72.0 * (node - 5) as f64,
+        _ => -54.0 + 72.0 * node as f64,
+    }
+}
+
+fn node(node: i16) -> (f64, f64) {
+    match node {
+        5..=9 => polar(RADIUS, angle(node)),
+        _ => polar(RADIUS * INNER, angle(node)),
+    }
+}
+
+// the line between two corners next to each other is an arc of the outer circle
+fn is_arc(from: i16, to: i16) -> bool {
+    (5..=9).contains(&from) && (5..=9).contains(&to) && matches!((from - to).rem_euclid(5), 1 | 4)
+}
+
+// point at {t} (0 to 1) of the line from {from} to {to}
+fn on_line(from: i16, to: i16, t: f64) -> (f64, f64) {
+    if is_arc(from, to) {
+        // shorter way around the circle
+        let delta = (angle(to) - angle(from) + 540.0).rem_euclid(360.0) - 180.0;
+        polar(RADIUS, angle(from) + delta * t)
+    } else {
+        let (start, end) = (node(from), node(to));
+        (
+            start.0 + (end.0 - start.0) * t,
+            start.1 + (end.1 - start.1) * t,
+        )
+    }
+}
+
+fn layout(definition: &BoardDefinition) -> HashMap<FIELD, (f64, f64)> {
+    let mut layout: HashMap<FIELD, (f64, f64)> = HashMap::with_capacity(definition.fields());
+
+    for id in definition.junctions.iter().chain(definition.corners.iter()) {
+        layout.insert([*id, 0, 0], node(*id));
+    }
+
+    for edge in &definition.edges {
+        for stop in 1..=edge.stops {
+            let t = stop as f64 / (edge.stops + 1) as f64;
+            layout.insert([edge.from, stop, edge.to], on_line(edge.from, edge.to, t));
+        }
+    }
+
+    layout
+}
+
+fn fill(figure: Figure) -> &'static str {
+    match rules::kind(figure) {
+        Some(FigureKind::Player { color, .. }) => COLORS[color as usize],
+        Some(FigureKind::GrayStopper) => GRAY_STOPPER,
+        _ => BLACK_STOPPER,
+    }
+}
+
+fn circle(svg: &mut String, (x, y): (f64, f64), radius: f64, attributes: &str) {
+    let _ = writeln!(
+        svg,
+        r#"<circle cx="{:.2}" cy="{:.2}" r="{:.2}" {}/>"#,
+        x, y, radius, attributes
+    );
+}
+
+// figures sharing a field are arranged around its center
+fn figures(svg: &mut String, center: (f64, f64), occupants: &[Figure]) {
+    let count = occupants.len();
+    for (index, figure) in occupants.iter().enumerate() {
+        let (position, radius) = match count {
+            1 => (center, FIGURE_RADIUS),
+            _ => {
+                let degrees = -90.0 + 360.0 * index as f64 / count as f64;
+                let angle = degrees.to_radians();
+                (
+                    (
+                        center.0 + FIGURE_RADIUS * angle.cos(),
+                        center.1 + FIGURE_RADIUS * angle.sin(),
+                    ),
+                    FIGURE_RADIUS * 0.7,
+                )
+            }
+        };
+
+        circle(
+            svg,
+            position,
+            radius,
+            &format!(
+                r#"fill="{}" stroke="{}" stroke-width="1.5" data-figure="{}""#,
+                fill(*figure),
+                FOREGROUND,
+                figure
+            ),
+        );
+    }
+}
+
+/*
+SVG of the state. {highlight} marks the source and destination of a move (e.g. the last move)
+with a line between them. Placements (source HAND) only mark the destination
+*/
+pub fn render(state: &GraphState, highlight: Option<&MOVE>) -> String {
+    let mut svg = String::with_capacity(16_384);
+    let height = SIZE + HAND_HEIGHT;
+
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {} {}" width="{}" height="{}">"#,
+        SIZE, height, SIZE, height
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect width="{}" height="{}" fill="{}"/>"#,
+        SIZE, height, BACKGROUND
+    );
+    circle(
+        &mut svg,
+        (CENTER, CENTER),
+        RADIUS,
+        &format!(r#"fill="none" stroke="{}" stroke-width="2""#, FOREGROUND),
+    );
+
+    // lines that aren't part of the outer circle
+    for edge in DEFINITION
+        .edges
+        .iter()
+        .filter(|edge| !is_arc(edge.from, edge.to))
+    {
+        let (start, end) = (node(edge.from), node(edge.to));
+        let _ = writeln!(
+            svg,
+            r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}" stroke="{}" stroke-width="2"/>"#,
+            start.0, start.1, end.0, end.1, FOREGROUND
+        );
+    }
+
+    // fields in sorted order to keep the output stable
+    let mut fields = LAYOUT.keys().copied().collect::<Vec<FIELD>>();
+    fields.sort();
+
+    for field in &fields {
+        let position = LAYOUT[field];
+        match field {
+            [corner, 0, 0] if *corner >= 5 => circle(
+                &mut svg,
+                position,
+                CORNER_RADIUS,
+                &format!(
+                    r#"fill="{}" stroke="{}" stroke-width="2""#,
+                    COLORS[(*corner - 5) as usize],
+                    FOREGROUND
+                ),
+            ),
+            // junctions are outlined with the color of the figures that have them as their goal
+            [junction, 0, 0] => circle(
+                &mut svg,
+                position,
+                JUNCTION_RADIUS,
+                &format!(
+                    r#"fill="{}" stroke="{}" stroke-width="3""#,
+                    FOREGROUND,
+                    COLORS[((*junction + 3) % 5) as usize]
+                ),
+            ),
+            _ => circle(
+                &mut svg,
+                position,
+                STOP_RADIUS,
+                &format!(
+                    r#"fill="{}" stroke="{}" stroke-width="1""#,
+                    FOREGROUND, BACKGROUND
+                ),
+            ),
+        };
+    }
+
+    if let Some(action) = highlight {
+        let (_, src, dest) = rules::split(action);
+        let marked = [src, dest]
+            .iter()
+            .filter_map(|field| LAYOUT.get(field).copied())
+            .collect::<Vec<(f64, f64)>>();
+
+        if let [start, end] = marked[..] {
+            let _ = writeln!(
+                svg,
+                r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}" stroke="{}" stroke-width="3" stroke-dasharray="6 4"/>"#,
+                start.0, start.1, end.0, end.1, HIGHLIGHT
+            );
+        }
+        for position in marked {
+            circle(
+                &mut svg,
+                position,
+                CORNER_RADIUS + 2.0,
+                &format!(r#"fill="none" stroke="{}" stroke-width="3""#, HIGHLIGHT),
+            );
+        }
+    }
+
+    for field in &fields {
+        let occupants = state.occupants(field);
+        if !occupants.is_empty() {
+            figures(&mut svg, LAYOUT[field], &occupants);
+        }
+    }
+
+    // stoppers that have to be placed
+    for (index, figure) in state.occupants(&HAND).iter().enumerate() {
+        figures(
+            &mut svg,
+            (
+                CENTER + (index as f64 - 2.0) * 3.0 * FIGURE_RADIUS,
+                SIZE + HAND_HEIGHT / 2.0,
+            ),
+            &[*figure],
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::graph::GRAPH;
+
+    fn count(svg: &str, pattern: &str) -> usize {
+        svg.matches(pattern).count()
+    }
+
+    #[test]
+    fn every_field_has_a_position() {
+        assert_eq!(LAYOUT.len(), GRAPH.vertices.len());
+
+        // stops of an arc lie on the outer circle
+        let (x, y) = LAYOUT[&[5, 2, 6]];
+        let distance = ((x - CENTER).powi(2) + (y - CENTER).powi(2)).sqrt();
+        assert!((distance - RADIUS).abs() < 0.01);
+
+        // junction 0 lies on the straight line from corner 5 to corner 7 (see board::DEFINITION)
+        let (corner, junction, other) =
+            (LAYOUT[&[5, 0, 0]], LAYOUT[&[0, 0, 0]], LAYOUT[&[7, 0, 0]]);
+        let cross = (junction.0 - corner.0) * (other.1 - corner.1)
+            - (junction.1 - corner.1) * (other.0 - corner.0);
+        assert!(cross.abs() < 1.0);
+    }
+
+    #[test]
+    fn renders_fields_and_figures() {
+        let state = GraphState::initial(2).unwrap();
+        let svg = render(&state, None);
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.ends_with("</svg>\n"));
+        // player figures and black stoppers (gray stoppers start off the board)
+        assert_eq!(count(&svg, "data-figure"), 15);
+        // outer circle, fields and figures
+        assert_eq!(count(&svg, "<circle"), 1 + GRAPH.vertices.len() + 15);
+        assert_eq!(count(&svg, HIGHLIGHT), 0);
+        assert_eq!(svg, render(&state, None));
+    }
+
+    #[test]
+    fn highlights_moves_and_placements() {
+        let state = GraphState::initial(2).unwrap();
+
+        let action = rules::assemble(1, [5, 0, 0], [0, 3, 5]);
+        assert_eq!(count(&render(&state, Some(&action)), HIGHLIGHT), 3);
+
+        let placement = rules::assemble(31, HAND, [0, 3, 5]);
+        assert_eq!(count(&render(&state, Some(&placement)), HIGHLIGHT), 1);
+    }
+}
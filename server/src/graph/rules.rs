@@ -0,0 +1,409 @@
+use super::bitboard::BOARD;
+use super::errors::RuleViolation;
+use super::graph::{is_on_board, Figure, GraphState};
+use super::models::{FIELD, HAND, MOVE, OFF_BOARD};
+use super::movegen::has_legal_move;
+use std::cmp::Reverse;
+use std::ops::RangeInclusive;
+
+/*
+Rules engine:
+    Takes a GraphState and a MOVE and either returns the resulting state or the violated rule
+    See templates/content/rules.html for the rules in prose
+
+    Figure ids (see db/model GameMove):
+        1-25: player figures. Player {pid} owns 5 * (pid - 1) + 1 ..= 5 * pid
+              The color of a figure is (id - 1) % 5. Figures of color c start on corner 5 + c
+              and have to reach the opposite junction (c + 2) % 5
+        26-30: gray stoppers
+        31-35: black stoppers
+*/
+pub const GRAY_STOPPERS: RangeInclusive<Figure> = 26..=30;
+pub const BLACK_STOPPERS: RangeInclusive<Figure> = 31..=35;
+// figure id of the moves storing the final points (see db/model GameMove)
+pub const SCORE_FIGURE: Figure = 42;
+// figure ids of the moves marking a passed turn and a resignation (see ws/actor give_up_turn)
+pub const PASS_FIGURE: Figure = 43;
+pub const RESIGN_FIGURE: Figure = 44;
+// markers don't move a figure -> both of their fields are OFF_BOARD
+pub const PASS: MOVE = ([-1, -1, -1, -1, -1, -1], PASS_FIGURE);
+pub const RESIGN: MOVE = ([-1, -1, -1, -1, -1, -1], RESIGN_FIGURE);
+// amount of figures a player has to bring to their goal to win (see graph/variant for other rule sets)
+pub const GOAL_AMOUNT: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FigureKind {
+    Player { pid: u8, color: u8 },
+    GrayStopper,
+    BlackStopper,
+}
+
+// What happened to the figure on the destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collision {
+    // destination was empty
+    Nothing,
+    // player figure was swapped onto the source field
+    Swap(Figure),
+    // black stopper was taken and has to be placed again
+    BlackStopper(Figure),
+    // gray stopper was taken and removed from the board
+    GrayStopper(Figure),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub state: GraphState,
+    // fields the figure passed including src and dest (only src and dest for replayed moves)
+    pub path: Vec<FIELD>,
+    pub collision: Collision,
+    // figure reached its goal and was removed from the board
+    pub goal: bool,
+    // gray stopper the player received for reaching the goal (None if all are in use)
+    pub gained: Option<Figure>,
+}
+
+impl Outcome {
+    // the player has to place stoppers before the turn is over
+    pub fn requires_placement(&self) -> bool {
+        !pending(&self.state).is_empty()
+    }
+}
+
+// Turn derived from games.state (see db/model Game)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Turn {
+    // waiting for players to join
+    Waiting,
+    // waiting for move of pid
+    Move(u8),
+    // waiting for pid to place stoppers
+    Placement(u8),
+    // finished with the given amount of winners
+    Finished(u8),
+}
+
+impl Turn {
+    pub fn from_state(state: i16) -> Option<Turn> {
+        match state {
+            0 => Some(Turn::Waiting),
+            1..=5 => Some(Turn::Move(state as u8)),
+            6..=10 => Some(Turn::Placement(state as u8 - 5)),
+            11..=16 => Some(Turn::Finished(state as u8 - 10)),
+            _ => None,
+        }
+    }
+
+    pub fn to_state(self) -> i16 {
+        match self {
+            Turn::Waiting => 0,
+            Turn::Move(pid) => pid.into(),
+            Turn::Placement(pid) => 5 + i16::from(pid),
+            Turn::Finished(winners) => 10 + i16::from(winners),
+        }
+    }
+}
+
+// turn after player {pid} acted. Players without any legal move are skipped
+pub fn next_turn(state: &GraphState, pid: u8, players: u8) -> Turn {
+    if players == 0 {
+        return Turn::Waiting;
+    } else if !pending(state).is_empty() {
+        return Turn::Placement(pid);
+    }
+
+    for offset in 1..=players {
+        let next = (pid - 1 + offset) % players + 1;
+        // a corrupted state shouldn't lock the game -> assume the player can move
+        if has_legal_move(state, next).unwrap_or(true) {
+            return Turn::Move(next);
+        }
+    }
+
+    Turn::Move(pid % players + 1)
+}
+
+// turn after player {pid} acted including the end of the game (see ranking)
+pub fn turn_after(state: &GraphState, pid: u8, players: u8) -> Turn {
+    match ranking(state, players) {
+        Some(ranking) => Turn::Finished(winners(&ranking)),
+        None => next_turn(state, pid, players),
+    }
+}
+
+// turn after player {pid} passed (see ws/bot). None if no other player has a legal move
+pub fn pass_turn(state: &GraphState, pid: u8, players: u8) -> Option<Turn> {
+    (1..players)
+        .map(|offset| (pid - 1 + offset) % players + 1)
+        .find(|next| has_legal_move(state, *next).unwrap_or(true))
+        .map(Turn::Move)
+}
+
+// score moves, passes and resignations are stored like moves but don't change the board
+pub fn is_marker(figure: Figure) -> bool {
+    figure == SCORE_FIGURE || figure == PASS_FIGURE || figure == RESIGN_FIGURE
+}
+
+pub fn kind(figure: Figure) -> Option<FigureKind> {
+    match figure {
+        1..=25 => Some(FigureKind::Player {
+            pid: (figure - 1) / 5 + 1,
+            color: (figure - 1) % 5,
+        }),
+        26..=30 => Some(FigureKind::GrayStopper),
+        31..=35 => Some(FigureKind::BlackStopper),
+        _ => None,
+    }
+}
+
+// figures owned by player {pid}
+pub fn figures_of(pid: u8) -> RangeInclusive<Figure> {
+    match pid {
+        1..=5 => 5 * (pid - 1) + 1..=5 * pid,
+        // empty range for invalid player ids
+        _ => RangeInclusive::new(1, 0),
+    }
+}
+
+// corner the figures of a color start on
+pub fn start_of(color: u8) -> FIELD {
+    [5 + color as i16, 0, 0]
+}
+
+// junction the figures of a color have to reach
+pub fn goal_of(color: u8) -> FIELD {
+    [((color + 2) % 5) as i16, 0, 0]
+}
+
+// stoppers that were taken and still have to be placed
+pub fn pending(state: &GraphState) -> Vec<Figure> {
+    state.occupants(&HAND)
+}
+
+// figures of player {pid} that reached their goal
+pub fn points(state: &GraphState, pid: u8) -> u8 {
+    figures_of(pid)
+        .filter(|figure| state.locate(*figure) == Some(OFF_BOARD))
+        .count() as u8
+}
+
+// points of the team of player {pid} (only their own points outside of team games)
+pub fn team_points(state: &GraphState, pid: u8) -> u8 {
+    let variant = state.variant();
+
+    (1..=state.players())
+        .filter(|other| variant.team(*other) == variant.team(pid))
+        .map(|other| points(state, other))
+        .sum()
+}
+
+/*
+final ranking as (pid, points, rank) once a player (or team) reached the goal amount of the variant
+Teammates share their points and with them their rank. Ties share a rank
+*/
+pub fn ranking(state: &GraphState, players: u8) -> Option<Vec<(u8, u8, u8)>> {
+    let goal_amount = state.variant().goal_amount();
+    if (1..=players).any(|pid| team_points(state, pid) >= goal_amount) {
+        Some(standings(state, players))
+    } else {
+        None
+    }
+}
+
+// ranking by the current points regardless of the goal amount (e.g. when nobody can move anymore)
+pub fn standings(state: &GraphState, players: u8) -> Vec<(u8, u8, u8)> {
+    let mut results = (1..=players)
+        .map(|pid| (pid, team_points(state, pid)))
+        .collect::<Vec<(u8, u8)>>();
+
+    // sort_by_key is stable -> players with equal points keep their seat order
+    results.sort_by_key(|(_, points)| Reverse(*points));
+
+    let mut ranking: Vec<(u8, u8, u8)> = Vec::with_capacity(results.len());
+    for (index, (pid, points)) in results.into_iter().enumerate() {
+        let rank = match ranking.last() {
+            Some((_, last_points, last_rank)) if *last_points == points => *last_rank,
+            _ => index as u8 + 1,
+        };
+        ranking.push((pid, points, rank));
+    }
+
+    ranking
+}
+
+// amount of players sharing the first rank
+pub fn winners(ranking: &[(u8, u8, u8)]) -> u8 {
+    ranking.iter().filter(|(_, _, rank)| *rank == 1).count() as u8
+}
+
+// Ko-rule: a figure may not immediately return to the field it came from
+pub fn is_reversal(last: &MOVE, action: &MOVE) -> bool {
+    let (last_figure, last_src, last_dest) = split(last);
+    let (figure, src, dest) = split(action);
+
+    last_figure == figure && src == last_dest && dest == last_src
+}
+
+// split MOVE into (figure, src, dest)
+pub fn split(action: &MOVE) -> (Figure, FIELD, FIELD) {
+    let (locations, figure) = action;
+    (
+        *figure,
+        [locations[0], locations[1], locations[2]],
+        [locations[3], locations[4], locations[5]],
+    )
+}
+
+// assemble MOVE from figure, src and dest (reverse of split)
+pub fn assemble(figure: Figure, src: FIELD, dest: FIELD) -> MOVE {
+    ([src[0], src[1], src[2], dest[0], dest[1], dest[2]], figure)
+}
+
+// shortest path between src and dest that doesn't cross any occupied field
+fn route(state: &GraphState, src: &FIELD, dest: &FIELD) -> Result<Vec<FIELD>, RuleViolation> {
+    let (src, dest) = match (BOARD.index(src), BOARD.index(dest)) {
+        (Some(src), Some(dest)) => (src, dest),
+        _ => return Err(RuleViolation::NoSuchField),
+    };
+    let occupancy = BOARD
+        .occupancy(state)
+        .map_err(|_| RuleViolation::NoSuchField)?;
+
+    match BOARD.path(src, dest, occupancy.occupied) {
+        Some(path) => Ok(path
+            .into_iter()
+            .filter_map(|index| BOARD.field(index))
+            .collect()),
+        None => Err(RuleViolation::NoFreePath),
+    }
+}
+
+// Validate and apply a move of a player figure made by player {pid}
+pub fn apply_move(state: &GraphState, pid: u8, action: &MOVE) -> Result<Outcome, RuleViolation> {
+    execute_move(state, pid, action, true)
+}
+
+// Apply a move without searching for a free path. Only meant for replaying trusted moves
+pub fn replay_move(state: &GraphState, pid: u8, action: &MOVE) -> Result<Outcome, RuleViolation> {
+    execute_move(state, pid, action, false)
+}
+
+fn execute_move(
+    state: &GraphState,
+    pid: u8,
+    action: &MOVE,
+    check_path: bool,
+) -> Result<Outcome, RuleViolation> {
+    let (figure, src, dest) = split(action);
+
+    // stoppers are only ever placed and players may only move their own figures
+    let color = match kind(figure) {
+        Some(FigureKind::Player { pid: owner, color }) if owner == pid => color,
+        Some(_) => return Err(RuleViolation::NotOwnFigure(figure)),
+        None => return Err(RuleViolation::NoSuchFigure(figure)),
+    };
+
+    if !pending(state).is_empty() {
+        return Err(RuleViolation::PlacementPending);
+    }
+
+    let location = match state.locate(figure) {
+        Some(location) => location,
+        None => return Err(RuleViolation::NoSuchFigure(figure)),
+    };
+
+    if !is_on_board(&location) {
+        return Err(RuleViolation::NotOnBoard(figure));
+    } else if location != src {
+        return Err(RuleViolation::SourceMismatch(figure));
+    } else if src == dest {
+        return Err(RuleViolation::SameField);
+    } else if BOARD.index(&dest).is_none() {
+        return Err(RuleViolation::NoSuchField);
+    }
+
+    let path = if check_path {
+        route(state, &src, &dest)?
+    } else {
+        vec![src, dest]
+    };
+
+    // resolve collision with the figure on the destination
+    let mut next = *state;
+    let collision = match state.occupants(&dest).as_slice() {
+        [] => Collision::Nothing,
+        [other] => match kind(*other) {
+            // figures of teammates don't swap (see graph/variant)
+            Some(FigureKind::Player { pid: owner, .. })
+                if state.variant().teammates(pid, owner) =>
+            {
+                return Err(RuleViolation::TeammateFigure(*other))
+            }
+            Some(FigureKind::Player { .. }) => {
+                next.relocate(*other, src);
+                Collision::Swap(*other)
+            }
+            Some(FigureKind::BlackStopper) => {
+                next.relocate(*other, HAND);
+                Collision::BlackStopper(*other)
+            }
+            Some(FigureKind::GrayStopper) => {
+                next.relocate(*other, OFF_BOARD);
+                Collision::GrayStopper(*other)
+            }
+            None => return Err(RuleViolation::NoSuchFigure(*other)),
+        },
+        // only starting corners can hold more than one figure
+        _ => return Err(RuleViolation::CrowdedField),
+    };
+
+    next.relocate(figure, dest);
+
+    // figures reaching their goal leave the board and grant a gray stopper
+    let goal = dest == goal_of(color);
+    let mut gained = None;
+    if goal {
+        next.relocate(figure, OFF_BOARD);
+        gained = GRAY_STOPPERS
+            .clone()
+            .find(|stopper| next.locate(*stopper) == Some(OFF_BOARD));
+
+        if let Some(stopper) = gained {
+            next.relocate(stopper, HAND);
+        }
+    }
+
+    Ok(Outcome {
+        state: next,
+        path,
+        collision,
+        goal,
+        gained,
+    })
+}
+
+// Validate and apply the placement of a taken stopper. src of the action has to be HAND
+pub fn apply_placement(state: &GraphState, action: &MOVE) -> Result<GraphState, RuleViolation> {
+    let (figure, src, dest) = split(action);
+
+    match kind(figure) {
+        Some(FigureKind::GrayStopper) | Some(FigureKind::BlackStopper) => (),
+        Some(FigureKind::Player { .. }) => return Err(RuleViolation::NotPending(figure)),
+        None => return Err(RuleViolation::NoSuchFigure(figure)),
+    };
+
+    if state.locate(figure) != Some(HAND) {
+        return Err(RuleViolation::NotPending(figure));
+    } else if src != HAND {
+        return Err(RuleViolation::SourceMismatch(figure));
+    } else if BOARD.index(&dest).is_none() {
+        return Err(RuleViolation::NoSuchField);
+    } else if !state.occupants(&dest).is_empty() {
+        return Err(RuleViolation::FieldOccupied);
+    }
+
+    let mut next = *state;
+    next.relocate(figure, dest);
+
+    Ok(next)
+}
@@ -0,0 +1,440 @@
+use super::bitboard::{indices, Bits, BOARD, MAX_FIELDS};
+use super::errors::GraphErr;
+use super::graph::GraphState;
+use super::models::{FIELD, HAND, MOVE, OFF_BOARD};
+use super::movegen::legal_moves;
+use super::notation::Position;
+use super::rules::{self, Turn};
+use super::variant::Variant;
+use super::zobrist;
+use derive_more::Display;
+use rand::Rng;
+use std::cmp::Reverse;
+use std::str::FromStr;
+
+/*
+Search:
+    Move selection for computer players (see ws/bot). Depth limited max^n search: every player
+    picks the move that maximizes their own score relative to the best opponent. Only the moves
+    with the best static evaluation (see Difficulty::breadth) are searched further
+
+    The static score of a player is GOAL_SCORE for every figure that reached its goal minus the
+    distance of the remaining figures to their goal on the empty board. Teammates share the sum of
+    their scores (see graph/variant)
+*/
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    #[display(fmt = "easy")]
+    Easy,
+    #[display(fmt = "medium")]
+    Medium,
+    #[display(fmt = "hard")]
+    Hard,
+}
+
+// score of every player indexed by pid - 1
+type Scores = [i32; 5];
+
+const GOAL_SCORE: i32 = 100;
+const WIN_SCORE: i32 = 10_000;
+
+impl Difficulty {
+    // level stored in users.bot (see db/model User)
+    pub fn from_level(level: i16) -> Option<Difficulty> {
+        match level {
+            1 => Some(Difficulty::Easy),
+            2 => Some(Difficulty::Medium),
+            3 => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+
+    pub fn level(self) -> i16 {
+        match self {
+            Difficulty::Easy => 1,
+            Difficulty::Medium => 2,
+            Difficulty::Hard => 3,
+        }
+    }
+
+    // plies searched including the own move
+    fn depth(self) -> u8 {
+        match self {
+            Difficulty::Easy => 1,
+            Difficulty::Medium => 2,
+            Difficulty::Hard => 3,
+        }
+    }
+
+    // moves searched further at every node (twice as many at the root)
+    fn breadth(self) -> usize {
+        match self {
+            Difficulty::Easy => 4,
+            Difficulty::Medium => 6,
+            Difficulty::Hard => 8,
+        }
+    }
+
+    // the chosen move is drawn from this many of the best moves
+    fn candidates(self) -> usize {
+        match self {
+            Difficulty::Easy => 4,
+            Difficulty::Medium => 2,
+            Difficulty::Hard => 1,
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Difficulty, String> {
+        match raw {
+            "easy" => Ok(Difficulty::Easy),
+            "medium" => Ok(Difficulty::Medium),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(format!("Unknown difficulty {}", raw)),
+        }
+    }
+}
+
+lazy_static! {
+    // distance of every field (by bitboard index) to the goal of every color
+    static ref GOAL_DISTANCES: Vec<Vec<i32>> = (0..5)
+        .map(|color| goal_distances(&rules::goal_of(color)))
+        .collect();
+}
+
+fn goal_distances(goal: &FIELD) -> Vec<i32> {
+    let mut distances = vec![0; MAX_FIELDS];
+    let mut visited: Bits = BOARD.bit(goal).unwrap_or(0);
+    let mut frontier = visited;
+    let mut distance = 0;
+
+    while frontier != 0 {
+        let mut next: Bits = 0;
+        for index in indices(frontier) {
+            distances[index] = distance;
+            next |= BOARD.neighbours(index);
+        }
+
+        frontier = next & !visited;
+        visited |= next;
+        distance += 1;
+    }
+
+    distances
+}
+
+fn scores(state: &GraphState, players: u8) -> Scores {
+    let mut own: Scores = [0; 5];
+
+    for pid in 1..=players {
+        for figure in rules::figures_of(pid) {
+            let color = ((figure - 1) % 5) as usize;
+            own[(pid - 1) as usize] += match state.locate(figure) {
+                Some(OFF_BOARD) => GOAL_SCORE,
+                Some(location) => match BOARD.index(&location) {
+                    Some(index) => -GOAL_DISTANCES[color][index],
+                    None => 0,
+                },
+                None => 0,
+            };
+        }
+    }
+
+    // teammates share their score (see graph/variant)
+    let variant = state.variant();
+    let mut scores: Scores = [0; 5];
+    for pid in 1..=players {
+        let mut score = (1..=players)
+            .filter(|other| variant.team(*other) == variant.team(pid))
+            .map(|other| own[(other - 1) as usize])
+            .sum::<i32>();
+
+        if rules::team_points(state, pid) >= variant.goal_amount() {
+            score += WIN_SCORE;
+        }
+        scores[(pid - 1) as usize] = score;
+    }
+
+    scores
+}
+
+// score of {pid} relative to the best opponent (teammates aren't opponents)
+fn relative(scores: &Scores, pid: u8, players: u8, variant: Variant) -> i32 {
+    let best = (1..=players)
+        .filter(|other| variant.team(*other) != variant.team(pid))
+        .map(|other| scores[(other - 1) as usize])
+        .max()
+        .unwrap_or(0);
+
+    scores[(pid - 1) as usize] - best
+}
+
+struct Child {
+    action: MOVE,
+    state: GraphState,
+    turn: Turn,
+    scores: Scores,
+}
+
+struct Search {
+    players: u8,
+    variant: Variant,
+    breadth: usize,
+}
+
+impl Search {
+    // every legal action of {pid} sorted by the static evaluation of {pid}
+    fn children(&self, state: &GraphState, pid: u8) -> Result<Vec<Child>, GraphErr> {
+        let mut children = legal_moves(state, pid)?
+            .into_iter()
+            .filter_map(|action| {
+                let next = if rules::split(&action).1 == HAND {
+                    rules::apply_placement(state, &action).ok()?
+                } else {
+                    rules::apply_move(state, pid, &action).ok()?.state
+                };
+
+                Some(Child {
+                    action,
+                    state: next,
+                    turn: rules::turn_after(&next, pid, self.players),
+                    scores: scores(&next, self.players),
+                })
+            })
+            .collect::<Vec<Child>>();
+
+        children
+            .sort_by_key(|child| Reverse(relative(&child.scores, pid, self.players, self.variant)));
+        Ok(children)
+    }
+
+    fn search(&self, child: &Child, depth: u8) -> Scores {
+        let pid = match child.turn {
+            Turn::Move(pid) | Turn::Placement(pid) if depth > 0 => pid,
+            _ => return child.scores,
+        };
+
+        let mut children = match self.children(&child.state, pid) {
+            Ok(children) => children,
+            Err(_) => return child.scores,
+        };
+        children.truncate(self.breadth);
+
+        children
+            .iter()
+            .map(|next| self.search(next, depth - 1))
+            .max_by_key(|scores| relative(scores, pid, self.players, self.variant))
+            .unwrap_or(child.scores)
+    }
+}
+
+// searched moves of the player whose turn it is with the scores they lead to (best first)
+fn rank(
+    position: &Position,
+    difficulty: Difficulty,
+    last: &[MOVE],
+    history: &[u64],
+) -> Result<Vec<(MOVE, Scores)>, GraphErr> {
+    let pid = match position.turn {
+        Turn::Move(pid) | Turn::Placement(pid) => pid,
+        _ => return Ok(Vec::new()),
+    };
+
+    let search = Search {
+        players: position.state.players(),
+        variant: position.state.variant(),
+        breadth: difficulty.breadth(),
+    };
+    let hash = history
+        .last()
+        .copied()
+        .unwrap_or_else(|| zobrist::hash(position));
+
+    let mut children = search
+        .children(&position.state, pid)?
+        .into_iter()
+        .filter(|child| {
+            let (_, src, _) = rules::split(&child.action);
+            if src == HAND {
+                return true;
+            }
+
+            let next = zobrist::update(hash, position, &Position::new(child.state, child.turn));
+            !last
+                .iter()
+                .any(|last| rules::is_reversal(last, &child.action))
+                && !zobrist::is_repetition(history, next)
+        })
+        .collect::<Vec<Child>>();
+    children.truncate(2 * difficulty.breadth());
+
+    let mut ranked = children
+        .iter()
+        .map(|child| (child.action, search.search(child, difficulty.depth() - 1)))
+        .collect::<Vec<(MOVE, Scores)>>();
+    // sort_by_key is stable -> equal moves keep the order of the static evaluation
+    ranked
+        .sort_by_key(|(_, scores)| Reverse(relative(scores, pid, search.players, search.variant)));
+
+    Ok(ranked)
+}
+
+/*
+Move (or placement) for the player whose turn it is. Moves violating the Ko-rule ({last} holds the
+last move of every figure of the player) or repeating a position of {history} for the third time
+(see graph/zobrist) are skipped. Returns None if there's nothing left to do
+*/
+pub fn choose_move<R: Rng>(
+    position: &Position,
+    difficulty: Difficulty,
+    last: &[MOVE],
+    history: &[u64],
+    rng: &mut R,
+) -> Result<Option<MOVE>, GraphErr> {
+    let mut ranked = rank(position, difficulty, last, history)?;
+    ranked.truncate(difficulty.candidates());
+
+    if ranked.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(ranked[rng.gen_range(0, ranked.len())].0))
+}
+
+/*
+Any legal action of the player whose turn it is for when choose_move filtered out every move.
+Moves that don't violate the Ko-rule come first, the server may still reject a repetition
+*/
+pub fn fallback_move(position: &Position, last: &[MOVE]) -> Result<Option<MOVE>, GraphErr> {
+    let pid = match position.turn {
+        Turn::Move(pid) | Turn::Placement(pid) => pid,
+        _ => return Ok(None),
+    };
+
+    let moves = legal_moves(&position.state, pid)?;
+    Ok(moves
+        .iter()
+        .find(|action| !last.iter().any(|last| rules::is_reversal(last, action)))
+        .or_else(|| moves.first())
+        .copied())
+}
+
+// amount of moves an analysis lists
+pub const ANALYSIS_MOVES: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    // score of every player relative to their best opponent in seat order (see relative)
+    pub evaluation: Vec<i32>,
+    // best moves of the player whose turn it is and their score
+    pub moves: Vec<(MOVE, i32)>,
+}
+
+/*
+Analysis of a position with the strongest search. The evaluation assumes the best move is played
+and falls back to the static evaluation when nobody can move (e.g. finished games)
+*/
+pub fn analyse(position: &Position, last: &[MOVE], history: &[u64]) -> Result<Analysis, GraphErr> {
+    let players = position.state.players();
+    let variant = position.state.variant();
+    let ranked = rank(position, Difficulty::Hard, last, history)?;
+
+    let evaluation = match ranked.first() {
+        Some((_, scores)) => *scores,
+        None => scores(&position.state, players),
+    };
+
+    let moves = match position.turn {
+        Turn::Move(pid) | Turn::Placement(pid) => ranked
+            .iter()
+            .take(ANALYSIS_MOVES)
+            .map(|(action, scores)| (*action, relative(scores, pid, players, variant)))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    Ok(Analysis {
+        evaluation: (1..=players)
+            .map(|pid| relative(&evaluation, pid, players, variant))
+            .collect(),
+        moves,
+    })
+}
+
+/*
+Analysis gives away the best moves -> it's only available where that isn't cheating: finished
+games and practice games (one human against computer players). Waiting games have nothing to analyse
+*/
+pub fn analysis_allowed(turn: Turn, practice: bool) -> bool {
+    match turn {
+        Turn::Finished(_) => true,
+        Turn::Move(_) | Turn::Placement(_) => practice,
+        Turn::Waiting => false,
+    }
+}
+
+// last move of every figure of {pid} (see rules::is_reversal)
+pub fn last_moves(moves: &[MOVE], pid: u8) -> Vec<MOVE> {
+    let figures = rules::figures_of(pid);
+    let mut last: Vec<MOVE> = Vec::with_capacity(5);
+
+    for action in moves.iter().rev() {
+        if figures.contains(&action.1) && !last.iter().any(|known| known.1 == action.1) {
+            last.push(*action);
+        }
+    }
+
+    last
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_positions_fall_back_to_legal_moves() {
+        let position = Position::new(GraphState::initial(2).unwrap(), Turn::Move(1));
+        let hash = zobrist::hash(&position);
+        let moves = legal_moves(&position.state, 1).unwrap();
+
+        // every move leads to a position that already occurred twice
+        let mut history: Vec<u64> = Vec::new();
+        for action in &moves {
+            let state = rules::apply_move(&position.state, 1, action).unwrap().state;
+            let next = Position::new(state, rules::next_turn(&state, 1, 2));
+            let repeated = zobrist::update(hash, &position, &next);
+            history.extend(&[repeated, repeated]);
+        }
+        history.push(hash);
+
+        let mut rng = rand::thread_rng();
+        for difficulty in &[Difficulty::Easy, Difficulty::Medium, Difficulty::Hard] {
+            assert_eq!(
+                choose_move(&position, *difficulty, &[], &history, &mut rng),
+                Ok(None)
+            );
+        }
+
+        let action = fallback_move(&position, &[]).unwrap().unwrap();
+        assert!(moves.contains(&action));
+    }
+
+    #[test]
+    fn fallback_prefers_moves_without_reversal() {
+        let position = Position::new(GraphState::initial(2).unwrap(), Turn::Move(1));
+        let moves = legal_moves(&position.state, 1).unwrap();
+        let (figure, src, dest) = rules::split(&moves[0]);
+        let last = rules::assemble(figure, dest, src);
+
+        let action = fallback_move(&position, &[last]).unwrap().unwrap();
+        assert!(!rules::is_reversal(&last, &action));
+    }
+
+    #[test]
+    fn finished_games_have_no_fallback() {
+        let position = Position::new(GraphState::initial(2).unwrap(), Turn::Finished(1));
+        assert_eq!(fallback_move(&position, &[]), Ok(None));
+    }
+}
@@ -0,0 +1,187 @@
+use super::graph::Figure;
+use super::rules::{self, FigureKind, GOAL_AMOUNT};
+use derive_more::Display;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/*
+Variant:
+    Rule set of a game (stored as games.variant, see db/model Game). Every GraphState knows the
+    variant it's played with, so the rules engine, the move generation and the notation don't need
+    it passed along
+
+    Standard: 2-5 players with five figures each. The first player to bring GOAL_AMOUNT figures
+              to their goal wins
+    Teams:    2v2 with four players. Seats 1 + 3 and 2 + 4 are teams. Figures of teammates don't
+              swap (a teammate's figure blocks its field) and the first team to bring
+              TEAM_GOAL_AMOUNT figures to their goals together wins
+    Quick:    2-5 players with three figures each. Player {pid} plays the colors pid - 1, pid and
+              pid + 1 (mod 5) and wins with QUICK_GOAL_AMOUNT figures
+*/
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Variant {
+    #[display(fmt = "standard")]
+    Standard,
+    #[display(fmt = "teams")]
+    Teams,
+    #[display(fmt = "quick")]
+    Quick,
+}
+
+// figures a team has to bring to their goals together
+pub const TEAM_GOAL_AMOUNT: u8 = 5;
+// figures a player has to bring to their goal in quick games
+pub const QUICK_GOAL_AMOUNT: u8 = 2;
+// figures every player plays with in quick games
+const QUICK_FIGURES: u8 = 3;
+
+impl Variant {
+    // id stored in games.variant
+    pub fn from_id(id: i16) -> Option<Variant> {
+        match id {
+            0 => Some(Variant::Standard),
+            1 => Some(Variant::Teams),
+            2 => Some(Variant::Quick),
+            _ => None,
+        }
+    }
+
+    pub fn id(self) -> i16 {
+        match self {
+            Variant::Standard => 0,
+            Variant::Teams => 1,
+            Variant::Quick => 2,
+        }
+    }
+
+    // amount of seated players a game can be started with
+    pub fn players(self) -> RangeInclusive<u8> {
+        match self {
+            Variant::Teams => 4..=4,
+            Variant::Standard | Variant::Quick => 2..=5,
+        }
+    }
+
+    // amount of players as written in messages (e.g. "2 to 5 players")
+    pub fn players_text(self) -> String {
+        let players = self.players();
+        if players.start() == players.end() {
+            format!("{} players", players.start())
+        } else {
+            format!("{} to {} players", players.start(), players.end())
+        }
+    }
+
+    // the figure takes part in the game (figures that don't are left out of the initial state)
+    pub fn plays(self, figure: Figure) -> bool {
+        match (self, rules::kind(figure)) {
+            (Variant::Quick, Some(FigureKind::Player { pid, color })) => {
+                (color + 6 - pid) % 5 < QUICK_FIGURES
+            }
+            (_, Some(_)) => true,
+            (_, None) => false,
+        }
+    }
+
+    // points (own or of the team) needed to win
+    pub fn goal_amount(self) -> u8 {
+        match self {
+            Variant::Standard => GOAL_AMOUNT,
+            Variant::Teams => TEAM_GOAL_AMOUNT,
+            Variant::Quick => QUICK_GOAL_AMOUNT,
+        }
+    }
+
+    // team of player {pid}. Every player is their own team outside of team games
+    pub fn team(self, pid: u8) -> u8 {
+        match self {
+            Variant::Teams => (pid + 1) % 2 + 1,
+            Variant::Standard | Variant::Quick => pid,
+        }
+    }
+
+    // both players are on the same team
+    pub fn teammates(self, first: u8, second: u8) -> bool {
+        first != second && self.team(first) == self.team(second)
+    }
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Variant, String> {
+        match raw {
+            "standard" => Ok(Variant::Standard),
+            "teams" => Ok(Variant::Teams),
+            "quick" => Ok(Variant::Quick),
+            _ => Err(format!("Unknown variant {}", raw)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::errors::RuleViolation;
+    use crate::graph::movegen::legal_moves;
+    use crate::graph::notation::Position;
+    use crate::graph::rules::Turn;
+
+    fn position(raw: &str) -> Position {
+        raw.parse::<Position>().expect("valid position")
+    }
+
+    #[test]
+    fn quick_players_spread_over_the_corners() {
+        let colors = |pid: u8| {
+            rules::figures_of(pid)
+                .filter(|figure| Variant::Quick.plays(*figure))
+                .map(|figure| (figure - 1) % 5)
+                .collect::<Vec<u8>>()
+        };
+
+        assert_eq!(colors(1), vec![0, 1, 2]);
+        assert_eq!(colors(4), vec![0, 3, 4]);
+        assert_eq!(colors(5), vec![0, 1, 4]);
+        assert!(rules::figures_of(3).all(|figure| Variant::Teams.plays(figure)));
+    }
+
+    #[test]
+    fn teammates_dont_swap() {
+        let board = "ab1,g,h,i,j/ae1,g,h,i,j/ab2,g,h,i,j/f,g,h,i,j/x,x,x,x,x/a,b,c,d,e m1";
+        let action = rules::assemble(1, [0, 1, 1], [0, 2, 1]);
+
+        let teams = position(&format!("{} teams", board)).state;
+        assert_eq!(
+            rules::apply_move(&teams, 1, &action),
+            Err(RuleViolation::TeammateFigure(11))
+        );
+        assert!(!legal_moves(&teams, 1).unwrap().contains(&action));
+
+        let standard = position(board).state;
+        assert!(rules::apply_move(&standard, 1, &action).is_ok());
+        assert!(legal_moves(&standard, 1).unwrap().contains(&action));
+    }
+
+    #[test]
+    fn win_conditions() {
+        let teams =
+            position("x,x,x,i,j/f,g,h,i,j/x,x,h,i,j/f,g,h,i,j/x,x,x,x,x/a,b,c,d,e m2 teams").state;
+        let ranking = rules::ranking(&teams, 4).unwrap();
+        assert_eq!(ranking, vec![(1, 5, 1), (3, 5, 1), (2, 0, 3), (4, 0, 3)]);
+        assert_eq!(rules::winners(&ranking), 2);
+        assert_eq!(rules::turn_after(&teams, 1, 4), Turn::Finished(2));
+
+        // the team is still one figure short
+        let teams =
+            position("x,x,h,i,j/f,g,h,i,j/x,x,h,i,j/f,g,h,i,j/x,x,x,x,x/a,b,c,d,e m2 teams").state;
+        assert_eq!(rules::ranking(&teams, 4), None);
+        assert_eq!(
+            rules::turn_after(&teams, 1, 4),
+            rules::next_turn(&teams, 1, 4)
+        );
+
+        let quick = position("x,x,h,-,-/-,g,h,i,-/x,x,x,x,x/a,b,c,d,e m2 quick").state;
+        assert_eq!(rules::ranking(&quick, 2), Some(vec![(1, 2, 1), (2, 0, 2)]));
+    }
+}
@@ -0,0 +1,112 @@
+use super::bitboard::{BOARD, MAX_FIELDS};
+use super::graph::Figure;
+use super::models::{FIELD, HAND, OFF_BOARD};
+use super::notation::Position;
+use super::rules::Turn;
+
+/*
+Zobrist hashing:
+    Every (figure, location) pair and every turn (see graph/rules Turn) gets a random key. The hash
+    of a position is the XOR of the keys of all figures and the turn, so a move only has to XOR out
+    the old and XOR in the new locations of the figures it touched (see update)
+
+    The keys are generated from a fixed seed because hashes are stored with the moves
+    (see db/model GameMove) and have to stay valid across restarts
+*/
+const SEED: u64 = 0x5045_4e54_4147_4f4e;
+// locations are the bitboard indices followed by OFF_BOARD and HAND
+const LOCATIONS: usize = MAX_FIELDS + 2;
+const FIGURES: usize = 35;
+// turns are keyed by their games.state (0 - 16)
+const TURNS: usize = 17;
+// a position may occur this often before moves into it are rejected
+pub const REPETITION_LIMIT: usize = 3;
+
+struct Keys {
+    figures: Vec<u64>,
+    turns: Vec<u64>,
+}
+
+// splitmix64 (see http://prng.di.unimi.it/splitmix64.c)
+fn next_key(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut key = *state;
+    key = (key ^ (key >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    key = (key ^ (key >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    key ^ (key >> 31)
+}
+
+impl Keys {
+    fn generate() -> Keys {
+        let mut state = SEED;
+
+        Keys {
+            figures: (0..FIGURES * LOCATIONS)
+                .map(|_| next_key(&mut state))
+                .collect(),
+            turns: (0..TURNS).map(|_| next_key(&mut state)).collect(),
+        }
+    }
+
+    fn figure(&self, figure: Figure, location: &FIELD) -> u64 {
+        let location = match *location {
+            OFF_BOARD => Some(MAX_FIELDS),
+            HAND => Some(MAX_FIELDS + 1),
+            _ => BOARD.index(location),
+        };
+
+        match (figure as usize, location) {
+            (1..=FIGURES, Some(location)) => {
+                self.figures[(figure as usize - 1) * LOCATIONS + location]
+            }
+            // figures that aren't part of the game don't change the hash
+            _ => 0,
+        }
+    }
+
+    fn turn(&self, turn: Turn) -> u64 {
+        self.turns
+            .get(turn.to_state() as usize)
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+lazy_static! {
+    static ref KEYS: Keys = Keys::generate();
+}
+
+pub fn hash(position: &Position) -> u64 {
+    (1..=FIGURES as Figure)
+        .filter_map(|figure| {
+            position
+                .state
+                .locate(figure)
+                .map(|location| KEYS.figure(figure, &location))
+        })
+        .fold(KEYS.turn(position.turn), |hash, key| hash ^ key)
+}
+
+// hash of {after} based on the hash of {before}. Only figures that changed their location are updated
+pub fn update(hash: u64, before: &Position, after: &Position) -> u64 {
+    let mut hash = hash ^ KEYS.turn(before.turn) ^ KEYS.turn(after.turn);
+
+    for figure in 1..=FIGURES as Figure {
+        let (old, new) = (before.state.locate(figure), after.state.locate(figure));
+        if old != new {
+            if let Some(location) = old {
+                hash ^= KEYS.figure(figure, &location);
+            }
+            if let Some(location) = new {
+                hash ^= KEYS.figure(figure, &location);
+            }
+        }
+    }
+
+    hash
+}
+
+// the position would occur for the REPETITION_LIMITth time
+pub fn is_repetition(history: &[u64], hash: u64) -> bool {
+    history.iter().filter(|previous| **previous == hash).count() + 1 >= REPETITION_LIMIT
+}
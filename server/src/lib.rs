@@ -0,0 +1,15 @@
+// loading macros
+#[macro_use]
+extern crate diesel;
+#[macro_use]
+extern crate lazy_static;
+
+// includes (the library exists for benches/ and is used by main.rs)
+pub mod api;
+pub mod auth;
+pub mod config;
+pub mod db;
+pub mod frontend;
+pub mod graph;
+pub mod server;
+pub mod ws;
@@ -1,22 +1,7 @@
-// loading macros
-#[macro_use]
-extern crate diesel;
-#[macro_use]
-extern crate lazy_static;
-
-// includes
-mod api;
-mod auth;
-mod graph;
-mod config;
-mod db;
-mod frontend;
-mod server;
-mod ws;
-
 // imports
-use crate::config::DEFAULT_CONFIG_NAME;
 use clap::{App, Arg, ArgMatches, SubCommand};
+use pentagame::config::{self, DEFAULT_CONFIG_NAME};
+use pentagame::{auth, graph, server};
 use std::path::Path;
 
 pub fn main() -> std::io::Result<()> {
@@ -41,11 +26,76 @@ pub fn main() -> std::io::Result<()> {
                         .takes_value(true),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("verify")
+                .about("replay and validate the move log of a game")
+                .arg(
+                    Arg::with_name("game")
+                        .value_name("GAME")
+                        .help("id of the game")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("print the game record of a running or finished game")
+                .arg(
+                    Arg::with_name("game")
+                        .value_name("GAME")
+                        .help("id of the game")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("replay a game record and print the final position")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("path of the game record")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("perft")
+                .about("count the positions the given amount of plies below a position")
+                .arg(
+                    Arg::with_name("position")
+                        .value_name("POSITION")
+                        .help("position in the notation of graph/notation")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("depth")
+                        .value_name("DEPTH")
+                        .help("amount of plies")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("board")
+                .about("validate a board definition (TOML or JSON)")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("path of the board definition")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
         .get_matches();
 
     // read config from 'cms.toml' and evaluate host
     match matches.subcommand_matches("serve") {
-        Some(_) => server::main()?,
+        Some(_) => {
+            server::select_board();
+            server::main()?
+        }
         None => (),
     };
 
@@ -66,5 +116,126 @@ pub fn main() -> std::io::Result<()> {
         None => (),
     };
 
+    match matches.subcommand_matches("verify") {
+        Some(subcommand_matches) => {
+            let gid = match subcommand_matches.value_of("game").unwrap().parse::<i32>() {
+                Ok(gid) => gid,
+                Err(_) => {
+                    eprintln!("GAME has to be a valid game id");
+                    return Ok(());
+                }
+            };
+
+            // stored games are played on the configured board
+            server::select_board();
+            let pool = config::DatabaseConfig::init_pool(config::CONFIG.clone())
+                .expect("Failed to acquire database connection pool");
+            let conn = pool.get().expect("Failed to acquire database connection");
+
+            match graph::graph::GraphState::verify_from_db(&conn, gid) {
+                Ok(state) => println!("Game {} is consistent: {:?}", gid, state),
+                Err(why) => println!("Game {} is inconsistent: {}", gid, why),
+            }
+        }
+        None => (),
+    };
+
+    match matches.subcommand_matches("export") {
+        Some(subcommand_matches) => {
+            let gid = match subcommand_matches.value_of("game").unwrap().parse::<i32>() {
+                Ok(gid) => gid,
+                Err(_) => {
+                    eprintln!("GAME has to be a valid game id");
+                    return Ok(());
+                }
+            };
+
+            // stored games are played on the configured board
+            server::select_board();
+            let pool = config::DatabaseConfig::init_pool(config::CONFIG.clone())
+                .expect("Failed to acquire database connection pool");
+            let conn = pool.get().expect("Failed to acquire database connection");
+
+            match graph::record::GameRecord::from_db(&conn, gid) {
+                Ok(record) => print!("{}", record),
+                Err(why) => eprintln!("Unable to export game {}: {}", gid, why),
+            }
+        }
+        None => (),
+    };
+
+    match matches.subcommand_matches("import") {
+        Some(subcommand_matches) => {
+            let raw = std::fs::read_to_string(subcommand_matches.value_of("file").unwrap())?;
+
+            match raw
+                .parse::<graph::record::GameRecord>()
+                .and_then(|record| record.replay())
+            {
+                Ok(position) => println!("{}", position),
+                Err(why) => eprintln!("Invalid game record: {}", why),
+            }
+        }
+        None => (),
+    };
+
+    match matches.subcommand_matches("perft") {
+        Some(subcommand_matches) => {
+            let position = match subcommand_matches
+                .value_of("position")
+                .unwrap()
+                .parse::<graph::notation::Position>()
+            {
+                Ok(position) => position,
+                Err(why) => {
+                    eprintln!("Invalid position: {}", why);
+                    return Ok(());
+                }
+            };
+            let depth = match subcommand_matches.value_of("depth").unwrap().parse::<u8>() {
+                Ok(depth) => depth,
+                Err(_) => {
+                    eprintln!("DEPTH has to be a number between 0 and 255");
+                    return Ok(());
+                }
+            };
+
+            // nodes below every move first (see graph/perft divide)
+            let start = std::time::Instant::now();
+            match graph::perft::divide(&position, depth) {
+                Ok(moves) => {
+                    for (action, nodes) in &moves {
+                        println!("{}: {}", graph::notation::format_move(action), nodes);
+                    }
+
+                    let nodes = match depth {
+                        0 => 1,
+                        _ => moves.iter().map(|(_, nodes)| nodes).sum(),
+                    };
+                    println!("\nNodes: {} ({:?})", nodes, start.elapsed());
+                }
+                Err(why) => eprintln!("Unable to search position: {}", why),
+            }
+        }
+        None => (),
+    };
+
+    match matches.subcommand_matches("board") {
+        Some(subcommand_matches) => {
+            let path = Path::new(subcommand_matches.value_of("file").unwrap());
+
+            match graph::board::BoardDefinition::load(path) {
+                Ok(definition) => println!(
+                    "Board '{}' is valid: {} fields on {} lines",
+                    definition.name,
+                    definition.fields(),
+                    definition.edges.len()
+                ),
+                Err(why) => eprintln!("Invalid board: {}", why),
+            }
+        }
+        None => (),
+    };
+
     Ok(())
 }
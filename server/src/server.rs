@@ -1,6 +1,7 @@
 use crate::api::routes as api_routes;
 use crate::config::{DatabaseConfig, CONFIG, SECRET_KEY};
 use crate::frontend::routes;
+use crate::graph::board::{self, BoardDefinition};
 use crate::graph::graph::Graph;
 use crate::ws::{actor::GameServer, routes as ws_routes};
 use actix::Actor;
@@ -11,8 +12,27 @@ use actix_web::{http::ContentEncoding, middleware::Compress, web, App, HttpServe
 use askama_actix::TemplateIntoResponse;
 use sodiumoxide::init;
 use std::io::Result;
+use std::path::Path;
+use std::process::exit;
 use time::Duration;
 
+/*
+Selects the board of the config (see ServerConfig board). Has to run before anything builds a graph,
+so serve, verify and export call it first. The other subcommands don't need a config and use the
+standard board
+*/
+pub fn select_board() {
+    if let Some(path) = &CONFIG.server.board {
+        match BoardDefinition::load(Path::new(path)).and_then(board::select) {
+            Ok(_) => println!("Using board '{}'", path),
+            Err(why) => {
+                eprintln!("ERROR: unable to use board '{}': {}", path, why);
+                exit(1)
+            }
+        }
+    }
+}
+
 #[actix_web::main]
 pub async fn main() -> Result<()> {
     // Start game server actor
@@ -81,6 +101,13 @@ pub async fn main() -> Result<()> {
                     .route("/create", web::get().to(routes::get_create_game))
                     .route("/create", web::post().to(routes::post_create_game))
                     .route("/view/{id}", web::get().to(routes::get_view_game))
+                    .route("/watch/{id}", web::get().to(routes::get_game_watch))
+                    .route("/{id}/board.svg", web::get().to(routes::get_game_board))
+                    .service(
+                        web::resource("/{id}/watch/")
+                            .data(server.clone())
+                            .to(ws_routes::spectator_route)
+                    )
             )
             .service(
                 web::scope("/users")
@@ -102,7 +129,10 @@ pub async fn main() -> Result<()> {
                     )
                     .service(
                         web::scope("/games")
-                            .route("/info", web::get().to(api_routes::get_game_meta)),
+                            .route("/info", web::get().to(api_routes::get_game_meta))
+                            .route("/position", web::get().to(api_routes::get_game_position))
+                            .route("/record", web::get().to(api_routes::get_game_record))
+                            .route("/analysis", web::get().to(api_routes::get_game_analysis)),
                     ),
             )
             .route("/", web::get().to(routes::get_index))
@@ -1,4 +1,7 @@
 pub mod actor;
+pub mod bot;
+pub mod chat;
 pub mod errors;
+pub mod protocol;
 pub mod routes;
 pub mod session;
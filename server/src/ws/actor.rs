@@ -1,54 +1,47 @@
-use std::convert::TryInto;
-
 use crate::api::errors::APIError;
 use crate::config::{DatabaseConfig, CONFIG};
 use crate::db::actions::{
-    fetch_latest_move, get_game, get_game_users, get_slim_game, get_user_game, make_new_move,
+    create_chat_message, fetch_latest_move, get_chat_messages, get_game_bots, get_game_hashes,
+    get_game_host, get_game_seats, get_game_state, get_game_users, get_slim_game, get_user_game,
+    is_kicked, is_muted, is_practice_game, kick_user, make_turn, set_muted, start_game,
 };
 use crate::frontend::routes::DbPool;
-use crate::graph::{graph::GraphState, graph::GRAPH, models::MOVE};
+use crate::graph::{
+    errors::RuleViolation,
+    graph::{Figure, GraphState},
+    models::{FIELD, HAND, MOVE},
+    movegen,
+    notation::{format_move, Position},
+    record::GameRecord,
+    rules::{self, Turn},
+    search::{analysis_allowed, Analysis, Difficulty},
+    zobrist,
+};
+use crate::ws::bot::{AnalyseMessage, BotPlayer, BotTurnMessage, BOT_THREADS};
+use crate::ws::chat::{self, Channel, ChatFilter, HISTORY_LENGTH};
+use crate::ws::protocol::{ChatEntry, Event, GameEvent, GameInfo, MoveData, Rank, ServerMessage};
 use actix::prelude::*;
-use diesel::result::Error as DBError;
+use diesel::{result::Error as DBError, PgConnection};
 use hashbrown::{HashMap, HashSet};
 use rand::{self, rngs::ThreadRng, Rng};
-use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
-// Game server sends this messages to session
-#[derive(Message, Serialize, Deserialize)]
-#[rtype(result = "()")]
-pub struct Message {
-    /*
-    | action | description                  | data                 |
-    | ------ | ---------------------------- | -------------------- |
-    | 0      | {user} joined room           | {"user": {user}}     |
-    | 1      | {user} made move             | {                    |
-    |        |                              |  "user": {user},     |
-    |        |                              |  "move": String      |
-    |        |                              | }                    |
-    | 2      | {user} needs to place figure | {"user": {user}}     |
-    | 3      | {user} placed figure         | {                    |
-    |        |                              |  "user": {user},     |
-    |        |                              |  "move": String      |
-    |        |                              | }                    |
-    | 4      | {user} disconnected          | {"user": {user}}     |
-    | 5      | Login                        | {                    |
-    |        |                              |  "name": String,     |
-    |        |                              |  "password": String, |
-    |        |                              | }                    |
-
-    Login is bound to websocket as cookie so no logout action required
-    */
-    pub action: u8,
-    pub data: HashMap<String, String>,
-}
+// How long the seat of a disconnected user is kept for a reconnect (see ws/protocol Resume)
+pub const RECONNECT_GRACE: Duration = Duration::from_secs(60);
+// How many events of a game are kept for replays
+const EVENT_BUFFER: usize = 256;
+// How often delayed events are sent to spectators (see ServerConfig spectator_delay)
+const SPECTATOR_INTERVAL: Duration = Duration::from_millis(500);
 
 // Message for game server communications
-
 #[derive(Message)]
-#[rtype(result = "Result<(String, String, i32, Vec<(Uuid, String)>), APIError>")]
+#[rtype(result = "Result<GameInfo, APIError>")]
 pub struct QueryGameMessage {
     pub gid: i32,
+    // spectators get the game as they currently see it (see GameServer views)
+    pub spectator: bool,
 }
 
 #[derive(Message)]
@@ -62,13 +55,119 @@ pub struct MakeMoveMessage {
     pub gid: i32,
 }
 
-// New game session is created
 #[derive(Message)]
-#[rtype(result = "Result<usize, APIError>")]
+#[rtype(result = "Result<Vec<FIELD>, APIError>")]
+pub struct LegalMovesMessage {
+    // related game id
+    pub gid: i32,
+    // figure to list legal destinations for
+    pub figure: Figure,
+}
+
+// best moves for the current position (see graph/search analysis_allowed)
+#[derive(Message)]
+#[rtype(result = "Result<(Position, Analysis), APIError>")]
+pub struct AnalysisMessage {
+    // related game id
+    pub gid: i32,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<bool, APIError>")]
+pub struct PlaceStopperMessage {
+    // user id from game session
+    pub uid: Uuid,
+    // stopper to place. Defaults to the first pending stopper
+    pub figure: Option<Figure>,
+    // field the stopper is placed on
+    pub dest: FIELD,
+    // related game id
+    pub gid: i32,
+}
+
+// computer player without an acceptable move gives up their turn (see ws/bot)
+#[derive(Message)]
+#[rtype(result = "Result<(), APIError>")]
+pub struct PassTurnMessage {
+    // user id of the computer player
+    pub uid: Uuid,
+    // related game id
+    pub gid: i32,
+}
+
+// host starts the game
+#[derive(Message)]
+#[rtype(result = "Result<i16, APIError>")]
+pub struct StartGameMessage {
+    // user id from game session
+    pub uid: Uuid,
+    // related game id
+    pub gid: i32,
+}
+
+// chat message of a session (see ws/chat)
+#[derive(Message)]
+#[rtype(result = "Result<(), APIError>")]
+pub struct SendChatMessage {
+    // user id and name from game session
+    pub uid: Uuid,
+    pub username: String,
+    // related game id
+    pub gid: i32,
+    pub channel: Channel,
+    pub message: String,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<ChatEntry>, APIError>")]
+pub struct ChatHistoryMessage {
+    // related game id
+    pub gid: i32,
+    pub channel: Channel,
+}
+
+// host mutes or unmutes a user in the chat
+#[derive(Message)]
+#[rtype(result = "Result<(), APIError>")]
+pub struct MuteMessage {
+    // user id from game session
+    pub uid: Uuid,
+    // related game id
+    pub gid: i32,
+    // muted user
+    pub user: Uuid,
+    pub muted: bool,
+}
+
+// host removes a user from the game
+#[derive(Message)]
+#[rtype(result = "Result<(), APIError>")]
+pub struct KickMessage {
+    // user id from game session
+    pub uid: Uuid,
+    // related game id
+    pub gid: i32,
+    // kicked user
+    pub user: Uuid,
+}
+
+// New game session is created. Returns the session id and the reconnect token
+#[derive(Message)]
+#[rtype(result = "Result<(usize, Uuid), APIError>")]
 pub struct Connect {
     // session id (== user id)
-    pub addr: Recipient<Message>,
+    pub addr: Recipient<ServerMessage>,
+    pub uid: Uuid,
+}
+
+// New spectator session is created. Returns the session id
+#[derive(Message)]
+#[rtype(result = "Result<usize, APIError>")]
+pub struct Watch {
+    pub addr: Recipient<ServerMessage>,
     pub uid: Uuid,
+    // watched game id
+    pub gid: i32,
 }
 
 // Session is disconnected
@@ -78,35 +177,67 @@ pub struct Disconnect {
     pub id: usize,
 }
 
-// Send message to specific game
+// Session takes over the seat of an earlier session. Returns the missed events or None if the
+// session can't be resumed
 #[derive(Message)]
-#[rtype(result = "Result<(), APIError>")]
-pub struct ClientMessage {
-    // Id of the client session
+#[rtype(result = "Option<Vec<Event>>")]
+pub struct ResumeMessage {
+    // id of the new session
     pub id: usize,
-    /*
-    action & data
-    ---
-    | action | description         | data                | host only |
-    | ------ | ------------------- | ----------------    | --------- |
-    | 1      | get game meta       | {}                  |     X     |
-    | 2      | make move           | {"move": [MOVE]}    |     X     |
-    | 3      | Place Stopper       | {"move": String}    |     X     |
-    | 4      | leave game          | {}                  |     X     |
-    | 5      | start game          | {"message": String} |     ✓     |
-    | 6      | stop game           | {"message": String} |     ✓     |
-    */
-    pub action: u8,
-    pub data: HashMap<String, String>,
-    // Game id (this may be used to reference rooms more easyl)
-    pub game: i32,
+    // user id from game session
+    pub uid: Uuid,
+    // related game id
+    pub gid: i32,
+    // reconnect token of the earlier session
+    pub token: Uuid,
+    // last event the client received
+    pub sequence: u64,
+}
+
+// seat of a user in a game that can be resumed with its reconnect token
+struct Seat {
+    uid: Uuid,
+    gid: i32,
+    // id of the last session on the seat
+    session: usize,
+    // when the last session disconnected (see RECONNECT_GRACE)
+    detached: Option<Instant>,
+}
+
+// game as spectators see it with a delay (see ws/protocol GameInfo)
+struct View {
+    state: i16,
+    position: String,
+    sequence: u64,
 }
 
 // `GameServer` manages  and responsible for coordinating game sessions
 pub struct GameServer {
-    sessions: HashMap<usize, Recipient<Message>>,
+    sessions: HashMap<usize, Recipient<ServerMessage>>,
+    users: HashMap<usize, Uuid>,
     games: HashMap<i32, HashSet<usize>>,
+    // read-only sessions of public games
+    spectators: HashMap<i32, HashSet<usize>>,
+    // events for spectators that aren't due yet and the games as they see them
+    delay: Option<Duration>,
+    delayed: VecDeque<(Instant, i32, Event)>,
+    views: HashMap<i32, View>,
     states: HashMap<i32, GraphState>,
+    // zobrist hashes of all positions of a game (see graph/zobrist)
+    hashes: HashMap<i32, Vec<u64>>,
+    // sequence of the last event of a game (see ws/protocol Event)
+    sequences: HashMap<i32, u64>,
+    // last events of a game for replays
+    events: HashMap<i32, VecDeque<Event>>,
+    // reconnect tokens of the sessions and their seats
+    tokens: HashMap<usize, Uuid>,
+    seats: HashMap<Uuid, Seat>,
+    // players that didn't reconnect in time. Their turns are given up for them (see expire)
+    absent: HashMap<i32, HashSet<Uuid>>,
+    // chat filters (see ws/chat)
+    filters: Vec<Box<dyn ChatFilter>>,
+    // computer players (see ws/bot)
+    bots: Addr<BotPlayer>,
     pool: DbPool,
     rng: ThreadRng,
 }
@@ -116,8 +247,25 @@ impl Default for GameServer {
         println!("Triggered default creation");
         GameServer {
             games: HashMap::new(),
+            spectators: HashMap::new(),
+            delay: CONFIG
+                .server
+                .spectator_delay
+                .filter(|delay| *delay > 0)
+                .map(Duration::from_secs),
+            delayed: VecDeque::new(),
+            views: HashMap::new(),
             states: HashMap::new(),
+            hashes: HashMap::new(),
+            sequences: HashMap::new(),
+            events: HashMap::new(),
+            tokens: HashMap::new(),
+            seats: HashMap::new(),
+            absent: HashMap::new(),
+            filters: chat::filters(&CONFIG.server),
+            bots: SyncArbiter::start(BOT_THREADS, || BotPlayer),
             sessions: HashMap::new(),
+            users: HashMap::new(),
             pool: DatabaseConfig::init_pool(CONFIG.clone())
                 .expect("Database pool failed to initialize"),
             rng: rand::thread_rng(),
@@ -127,20 +275,537 @@ impl Default for GameServer {
 
 impl GameServer {
     // Send message to all users in the room
-    fn send_message(&self, game: &i32, action: u8, data: HashMap<String, String>, skip_id: usize) {
+    fn send_message(&self, game: &i32, message: ServerMessage, skip_id: usize) {
         if let Some(sessions) = self.games.get(game) {
             for id in sessions {
                 if *id != skip_id {
                     if let Some(addr) = self.sessions.get(id) {
-                        let _ = addr.do_send(Message {
-                            action,
-                            data: data.clone(),
-                        });
+                        let _ = addr.do_send(message.clone());
                     }
                 }
             }
         }
     }
+
+    // Send message to all sessions of a user in the room, including spectator sessions
+    fn send_user_message(&self, game: &i32, user: &Uuid, message: ServerMessage) {
+        let sessions = self
+            .games
+            .get(game)
+            .into_iter()
+            .chain(self.spectators.get(game));
+        for id in sessions.flatten() {
+            if self.users.get(id) == Some(user) {
+                if let Some(addr) = self.sessions.get(id) {
+                    let _ = addr.do_send(message.clone());
+                }
+            }
+        }
+    }
+
+    // Push an event with the next sequence number to all sessions of the game
+    fn broadcast(&mut self, gid: i32, event: GameEvent) {
+        let sequence = self.sequences.entry(gid).or_insert(0);
+        *sequence += 1;
+
+        let event = Event {
+            sequence: *sequence,
+            event,
+        };
+
+        let buffer = self.events.entry(gid).or_default();
+        buffer.push_back(event.clone());
+        if buffer.len() > EVENT_BUFFER {
+            buffer.pop_front();
+        }
+
+        self.send_message(&gid, ServerMessage::Event(event.clone()), 0);
+        match self.delay {
+            Some(delay) => self.delayed.push_back((Instant::now() + delay, gid, event)),
+            None => self.send_spectators(gid, event),
+        }
+    }
+
+    // Send an event to all spectators of the game
+    fn send_spectators(&mut self, gid: i32, event: Event) {
+        if let Some(view) = self.views.get_mut(&gid) {
+            view.sequence = event.sequence;
+            match &event.event {
+                GameEvent::GameStarted { state, position }
+                | GameEvent::TurnChanged { state, position }
+                | GameEvent::GameFinished {
+                    state, position, ..
+                } => {
+                    view.state = *state;
+                    view.position = position.clone();
+                }
+                _ => (),
+            }
+        }
+
+        if let Some(spectators) = self.spectators.get(&gid) {
+            let message = ServerMessage::Event(event);
+            for id in spectators {
+                if let Some(addr) = self.sessions.get(id) {
+                    let _ = addr.do_send(message.clone());
+                }
+            }
+        }
+    }
+
+    // send the delayed events that are due to the spectators
+    fn send_delayed(&mut self) {
+        let now = Instant::now();
+        while let Some((due, _, _)) = self.delayed.front() {
+            if *due > now {
+                break;
+            }
+
+            if let Some((_, gid, event)) = self.delayed.pop_front() {
+                self.send_spectators(gid, event);
+            }
+        }
+    }
+
+    // remember how spectators see a game loaded from the database. Only needed with a delay
+    fn load_view(
+        &mut self,
+        conn: &PgConnection,
+        gid: i32,
+        state: GraphState,
+    ) -> Result<(), APIError> {
+        if self.delay.is_none() || self.views.contains_key(&gid) {
+            return Ok(());
+        }
+
+        let game_state = get_game_state(conn, gid)?;
+        let view = View {
+            state: game_state,
+            position: Position::from_game(state, game_state)?.to_string(),
+            sequence: self.sequences.get(&gid).copied().unwrap_or(0),
+        };
+        self.views.insert(gid, view);
+        Ok(())
+    }
+
+    // events of a game after {sequence}. None if some of them aren't buffered anymore
+    fn replay(&self, gid: i32, sequence: u64) -> Option<Vec<Event>> {
+        let last = self.sequences.get(&gid).copied().unwrap_or(0);
+        if sequence > last {
+            return None;
+        } else if sequence == last {
+            return Some(Vec::new());
+        }
+
+        let missed = (last - sequence) as usize;
+        let buffer = self.events.get(&gid)?;
+        if buffer.len() < missed {
+            return None;
+        }
+
+        Some(buffer.iter().skip(buffer.len() - missed).cloned().collect())
+    }
+
+    // the user didn't reconnect in time (see RECONNECT_GRACE)
+    fn expire(&mut self, token: Uuid, detached: Instant, server: Addr<GameServer>) {
+        let (gid, user) = match self.seats.get(&token) {
+            Some(seat) if seat.detached == Some(detached) => (seat.gid, seat.uid),
+            _ => return,
+        };
+
+        self.seats.remove(&token);
+        self.broadcast(gid, GameEvent::Disconnected { user });
+
+        // the user may still be connected in another session
+        if !self
+            .seats
+            .values()
+            .any(|seat| seat.uid == user && seat.gid == gid)
+        {
+            // the game would wait for them forever otherwise
+            self.absent.entry(gid).or_default().insert(user);
+            let result = self.pool.get().map_err(APIError::from).and_then(|conn| {
+                match self.absent_turn(&conn, gid)? {
+                    Some(uid) => self.give_up_turn(&conn, gid, uid, server),
+                    None => Ok(()),
+                }
+            });
+            if let Err(why) = result {
+                eprintln!("Unable to give up turn of game {}: {}", gid, why);
+            }
+        }
+
+        self.release(gid);
+    }
+
+    // drop everything kept about a game once nobody is connected to it or can resume a seat
+    fn release(&mut self, gid: i32) {
+        let connected = |sessions: Option<&HashSet<usize>>| sessions.map_or(0, HashSet::len) > 0;
+        if connected(self.games.get(&gid))
+            || connected(self.spectators.get(&gid))
+            || self.seats.values().any(|seat| seat.gid == gid)
+        {
+            return;
+        }
+
+        self.games.remove(&gid);
+        self.spectators.remove(&gid);
+        self.states.remove(&gid);
+        self.hashes.remove(&gid);
+        self.sequences.remove(&gid);
+        self.events.remove(&gid);
+        self.views.remove(&gid);
+        self.absent.remove(&gid);
+        self.delayed.retain(|(_, game, _)| *game != gid);
+    }
+
+    // the seat of the user expired and they didn't connect again (see expire)
+    fn is_absent(&self, gid: i32, uid: Uuid) -> bool {
+        matches!(self.absent.get(&gid), Some(absent) if absent.contains(&uid))
+    }
+
+    // absent player whose turn it is (see expire)
+    fn absent_turn(&self, conn: &PgConnection, gid: i32) -> Result<Option<Uuid>, APIError> {
+        let pid = match Turn::from_state(get_game_state(conn, gid)?) {
+            Some(Turn::Move(pid)) | Some(Turn::Placement(pid)) => pid,
+            _ => return Ok(None),
+        };
+
+        Ok(get_game_seats(conn, gid)?
+            .get((pid - 1) as usize)
+            .copied()
+            .filter(|uid| self.is_absent(gid, *uid)))
+    }
+
+    // the turn changed to {next} (see ws/protocol GameEvent::TurnChanged)
+    fn announce_turn(&mut self, gid: i32, state: GraphState, next: Turn) {
+        let event = GameEvent::TurnChanged {
+            state: next.to_state(),
+            position: Position::new(state, next).to_string(),
+        };
+        self.broadcast(gid, event);
+    }
+
+    // random id of a new session that isn't taken yet
+    // 0 is never a session id to enable 0 as placeholder for nobody when skipping
+    fn session_id(&mut self) -> usize {
+        loop {
+            let id = self.rng.gen_range(1, usize::MAX);
+            if !self.sessions.contains_key(&id) {
+                return id;
+            }
+        }
+    }
+
+    // hash of the position after a move based on the position before it (see graph/zobrist)
+    fn next_hash(&self, gid: i32, before: &Position, after: &Position) -> u64 {
+        match self.hashes.get(&gid).and_then(|hashes| hashes.last()) {
+            Some(hash) => zobrist::update(*hash, before, after),
+            None => zobrist::hash(after),
+        }
+    }
+
+    // turn of the computer player that has to act next (None if it's the turn of a human)
+    fn bot_turn(
+        &self,
+        conn: &PgConnection,
+        gid: i32,
+        server: Addr<GameServer>,
+    ) -> Result<Option<BotTurnMessage>, APIError> {
+        let turn = match Turn::from_state(get_game_state(conn, gid)?) {
+            Some(turn) => turn,
+            None => return Ok(None),
+        };
+        let pid = match turn {
+            Turn::Move(pid) | Turn::Placement(pid) => pid,
+            _ => return Ok(None),
+        };
+
+        let uid = match get_game_seats(conn, gid)?.get((pid - 1) as usize) {
+            Some(uid) => *uid,
+            None => return Ok(None),
+        };
+        let difficulty = match get_game_bots(conn, gid)?
+            .into_iter()
+            .find(|(bot, _)| *bot == uid)
+            .and_then(|(_, level)| Difficulty::from_level(level))
+        {
+            Some(difficulty) => difficulty,
+            None => return Ok(None),
+        };
+
+        let state = match self.states.get(&gid) {
+            Some(state) => *state,
+            None => {
+                return Err(APIError::InternalError(
+                    "Game state wasn't loaded".to_owned(),
+                ));
+            }
+        };
+
+        Ok(Some(BotTurnMessage {
+            uid,
+            gid,
+            position: Position::new(state, turn),
+            difficulty,
+            last: last_moves(conn, gid, uid, pid)?,
+            history: self.hashes.get(&gid).cloned().unwrap_or_default(),
+            server,
+        }))
+    }
+
+    // analysis request for the current position of a game
+    fn analysis(&self, conn: &PgConnection, gid: i32) -> Result<AnalyseMessage, APIError> {
+        let turn = match Turn::from_state(get_game_state(conn, gid)?) {
+            Some(turn) => turn,
+            None => return Err(RuleViolation::NotRunning.into()),
+        };
+
+        if !analysis_allowed(turn, is_practice_game(conn, gid)?) {
+            return Err(APIError::AuthorizationError(
+                "Analysis is only available for finished and practice games".to_owned(),
+            ));
+        }
+
+        // same source as the analysis of the API (see api/routes get_game_analysis)
+        let record = GameRecord::from_db(conn, gid)?;
+        let (position, last, history) = record.analysis_input(record.moves.len())?;
+
+        Ok(AnalyseMessage {
+            position,
+            last,
+            history,
+        })
+    }
+
+    // let the computer player search for its move if it's their turn. Absent players give it up
+    fn schedule_bot(&self, conn: &PgConnection, gid: i32, server: Addr<GameServer>) {
+        match self.bot_turn(conn, gid, server.clone()) {
+            Ok(Some(turn)) => self.bots.do_send(turn),
+            Ok(None) => match self.absent_turn(conn, gid) {
+                Ok(Some(uid)) => server.do_send(PassTurnMessage { uid, gid }),
+                Ok(None) => (),
+                Err(why) => eprintln!("Unable to check turn of game {}: {}", gid, why),
+            },
+            Err(why) => eprintln!("Unable to schedule bot of game {}: {}", gid, why),
+        }
+    }
+
+    /*
+    The player can't (or won't) act (e.g. every move of a computer player repeats a position for the
+    third time). The turn goes to the next player that can move. If nobody else can move, the pass
+    repeats a position or the player has to place stoppers, they resign and the game ends with the
+    current points instead of waiting forever
+    */
+    fn give_up_turn(
+        &mut self,
+        conn: &PgConnection,
+        gid: i32,
+        uid: Uuid,
+        server: Addr<GameServer>,
+    ) -> Result<(), APIError> {
+        let (seats, pid) = seat_of(conn, gid, uid)?;
+
+        let current = get_game_state(conn, gid)?;
+        let placement = match Turn::from_state(current) {
+            Some(Turn::Move(active)) if active == pid => false,
+            Some(Turn::Placement(active)) if active == pid => true,
+            Some(Turn::Move(active)) | Some(Turn::Placement(active)) => {
+                return Err(RuleViolation::OutOfTurn(active).into());
+            }
+            _ => return Err(RuleViolation::NotRunning.into()),
+        };
+
+        let state = match self.states.get(&gid) {
+            Some(state) => *state,
+            None => {
+                return Err(APIError::InternalError(
+                    "Game state wasn't loaded".to_owned(),
+                ));
+            }
+        };
+
+        // passing players could take turns forever -> passes may not repeat positions either
+        let players = seats.len() as u8;
+        let pass = rules::pass_turn(&state, pid, players)
+            .filter(|_| !placement)
+            .and_then(|next| {
+                let hash = self.next_hash(
+                    gid,
+                    &Position::new(state, Turn::Move(pid)),
+                    &Position::new(state, next),
+                );
+
+                match self.hashes.get(&gid) {
+                    Some(hashes) if zobrist::is_repetition(hashes, hash) => None,
+                    _ => Some((next, hash)),
+                }
+            });
+
+        // passes and resignations are stored as markers, so records can replay them (see graph/record)
+        match pass {
+            Some((next, hash)) => {
+                commit_turn(
+                    conn,
+                    gid,
+                    &[(uid, rules::PASS, Some(hash as i64))],
+                    current,
+                    next,
+                )?;
+
+                // same position with another player to move -> stored like the hash of a move
+                self.hashes.entry(gid).or_default().push(hash);
+                self.announce_turn(gid, state, next);
+            }
+            None => {
+                let ranking = rules::standings(&state, players);
+                let turn = Turn::Finished(rules::winners(&ranking));
+                let mut moves = vec![(uid, rules::RESIGN, None)];
+                moves.extend(score_moves(&seats, &ranking));
+                commit_turn(conn, gid, &moves, current, turn)?;
+                self.announce_ranking(gid, &seats, state, turn, &ranking);
+            }
+        }
+
+        self.schedule_bot(conn, gid, server);
+
+        Ok(())
+    }
+
+    // prompt the player to place the stoppers they took
+    fn request_placement(&mut self, gid: i32, uid: &Uuid, state: &GraphState) {
+        let event = GameEvent::PlacementRequired {
+            user: *uid,
+            figures: rules::pending(state),
+        };
+        self.broadcast(gid, event);
+    }
+
+    // notify every session of the game about the final ranking (see score_moves)
+    fn announce_ranking(
+        &mut self,
+        gid: i32,
+        seats: &[Uuid],
+        final_state: GraphState,
+        turn: Turn,
+        ranking: &[(u8, u8, u8)],
+    ) {
+        let ranking = ranking
+            .iter()
+            .map(|(pid, points, rank)| Rank {
+                user: seats[(*pid - 1) as usize],
+                points: *points,
+                rank: *rank,
+            })
+            .collect::<Vec<Rank>>();
+        let event = GameEvent::GameFinished {
+            state: turn.to_state(),
+            position: Position::new(final_state, turn).to_string(),
+            ranking,
+        };
+        self.broadcast(gid, event);
+    }
+}
+
+// only the host moderates the game and nobody moderates the host
+fn moderate(conn: &PgConnection, gid: i32, uid: Uuid, user: Uuid) -> Result<(), APIError> {
+    if get_game_host(conn, gid)? != uid {
+        return Err(APIError::AuthorizationError(
+            "Only the host can moderate the game".to_owned(),
+        ));
+    } else if user == uid {
+        return Err(APIError::ValidationError(
+            "The host can't moderate themselves".to_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
+// seats of the game and pid of the user
+fn seat_of(conn: &PgConnection, gid: i32, uid: Uuid) -> Result<(Vec<Uuid>, u8), APIError> {
+    let seats = get_game_seats(conn, gid)?;
+
+    match seats.iter().position(|seat| *seat == uid) {
+        Some(index) => Ok((seats, (index + 1) as u8)),
+        None => Err(APIError::AuthorizationError(
+            "You aren't seated in this game".to_owned(),
+        )),
+    }
+}
+
+// last move of every figure of the player for the Ko-rule (see MakeMoveMessage handler)
+fn last_moves(conn: &PgConnection, gid: i32, uid: Uuid, pid: u8) -> Result<Vec<MOVE>, APIError> {
+    let mut last: Vec<MOVE> = Vec::with_capacity(5);
+
+    for figure in rules::figures_of(pid) {
+        match fetch_latest_move(conn, gid, uid, figure.into()) {
+            Ok(action) => last.push(action),
+            Err(DBError::NotFound { .. }) => (),
+            Err(why) => return Err(why.into()),
+        }
+    }
+
+    Ok(last)
+}
+
+// rule violation naming the rejected move (see graph/notation)
+fn rejected(action: &MOVE, reason: RuleViolation) -> APIError {
+    APIError::ValidationError(format!("{}: {}", format_move(action), reason))
+}
+
+// positions of the game so far: the initial position followed by the stored hashes of the moves
+fn load_hashes(conn: &PgConnection, gid: i32, current: &Position) -> Result<Vec<u64>, APIError> {
+    let initial = Position::new(GraphState::initial_from_db(conn, gid)?, Turn::Move(1));
+
+    let mut hashes = vec![zobrist::hash(&initial)];
+    hashes.extend(
+        get_game_hashes(conn, gid)?
+            .into_iter()
+            .map(|hash| hash as u64),
+    );
+
+    // moves made before hashes were stored are missing -> continue from the current position
+    let hash = zobrist::hash(current);
+    if hashes.last() != Some(&hash) {
+        hashes.push(hash);
+    }
+
+    Ok(hashes)
+}
+
+// final ranking as moves of figure 42 (see db/model) stored together with the last move
+fn score_moves(seats: &[Uuid], ranking: &[(u8, u8, u8)]) -> Vec<(Uuid, MOVE, Option<i64>)> {
+    ranking
+        .iter()
+        .map(|(pid, points, rank)| {
+            (
+                seats[(*pid - 1) as usize],
+                (
+                    [*pid as i16, *points as i16, *rank as i16, -1, -1, -1],
+                    rules::SCORE_FIGURE,
+                ),
+                None,
+            )
+        })
+        .collect()
+}
+
+// insert moves and hand over the turn atomically (see db/actions make_turn)
+fn commit_turn(
+    conn: &PgConnection,
+    gid: i32,
+    moves: &[(Uuid, MOVE, Option<i64>)],
+    current: i16,
+    next: Turn,
+) -> Result<(), APIError> {
+    match make_turn(conn, gid, moves, current, next.to_state()) {
+        Ok(_) => Ok(()),
+        Err(DBError::RollbackTransaction) => Err(APIError::ValidationError(
+            "The game changed while your move was processed".to_owned(),
+        )),
+        Err(why) => Err(why.into()),
+    }
 }
 
 // Make actor from `GameServer`
@@ -148,19 +813,25 @@ impl Actor for GameServer {
     // We are going to use simple Context, we just need ability to communicate
     // with other actors.
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        if self.delay.is_some() {
+            ctx.run_interval(SPECTATOR_INTERVAL, |act, _| act.send_delayed());
+        }
+    }
 }
 
 // Handler for Connect message.
 //
 // Register new session and assign unique id to this session
 impl Handler<Connect> for GameServer {
-    type Result = Result<usize, APIError>;
+    type Result = Result<(usize, Uuid), APIError>;
 
-    fn handle(&mut self, msg: Connect, _: &mut Context<Self>) -> Self::Result {
-        // register session with random id. The +1 ensures that 0 is never a session id
-        // to enable 0 as placeholder for nobody when skipping
-        let id = self.rng.gen::<usize>() + 1_usize;
+    fn handle(&mut self, msg: Connect, ctx: &mut Context<Self>) -> Self::Result {
+        // register session with random id (see session_id)
+        let id = self.session_id();
         self.sessions.insert(id, msg.addr);
+        self.users.insert(id, msg.uid);
 
         // add to group
         let conn = self.pool.get()?;
@@ -170,6 +841,11 @@ impl Handler<Connect> for GameServer {
                 return Err(APIError::ValidationError("Not joined any game".to_owned()));
             }
         };
+        if is_kicked(&conn, gid, msg.uid)? {
+            return Err(APIError::AuthorizationError(
+                "You were kicked from this game".to_owned(),
+            ));
+        }
 
         /*
         rebuilding state
@@ -177,12 +853,17 @@ impl Handler<Connect> for GameServer {
         */
         let state = match GraphState::build_from_db(&conn, gid) {
             Ok(state) => state,
-            Err(_) => {
+            Err(why) => {
+                eprintln!("GraphErr: {}", why);
                 return Err(APIError::InternalError(
                     "Unable to build graph state from database".to_owned(),
                 ));
             }
         };
+        let current = Position::from_game(state, get_game_state(&conn, gid)?)?;
+        let hashes = load_hashes(&conn, gid, &current)?;
+        self.hashes.insert(gid, hashes);
+        self.load_view(&conn, gid, state)?;
 
         match self.games.get_mut(&gid) {
             Some(game) => {
@@ -194,11 +875,31 @@ impl Handler<Connect> for GameServer {
                 new_game.insert(id);
                 self.games.insert(gid, new_game);
                 self.states.insert(gid, state);
+
+                // the server may have restarted while a computer player was searching
+                self.schedule_bot(&conn, gid, ctx.address());
             }
         }
 
+        if let Some(absent) = self.absent.get_mut(&gid) {
+            absent.remove(&msg.uid);
+        }
+
+        // hand out a reconnect token for the seat
+        let token = Uuid::new_v4();
+        self.tokens.insert(id, token);
+        self.seats.insert(
+            token,
+            Seat {
+                uid: msg.uid,
+                gid,
+                session: id,
+                detached: None,
+            },
+        );
+
         // send id back
-        Ok(id)
+        Ok((id, token))
     }
 }
 
@@ -206,56 +907,125 @@ impl Handler<Connect> for GameServer {
 impl Handler<Disconnect> for GameServer {
     type Result = ();
 
-    fn handle(&mut self, msg: Disconnect, _: &mut Context<Self>) {
-        let mut games: Vec<i32> = Vec::new();
-
+    fn handle(&mut self, msg: Disconnect, ctx: &mut Context<Self>) {
         // remove address
+        self.users.remove(&msg.id);
+        let mut left: Vec<i32> = Vec::new();
         if self.sessions.remove(&msg.id).is_some() {
             // remove session from all rooms
-            for (name, sessions) in &mut self.games {
+            for (gid, sessions) in self.games.iter_mut().chain(self.spectators.iter_mut()) {
                 if sessions.remove(&msg.id) {
-                    games.push(name.to_owned());
+                    left.push(*gid);
                 }
             }
         }
 
-        // send message to other users
-        let mut data: HashMap<String, String> = HashMap::with_capacity(1);
-        data.insert(String::from("user"), msg.id.to_string());
-        for game in games {
-            self.send_message(&game, 3_u8, data.clone(), 0);
+        // keep the seat for a reconnect. Other users are notified when the grace period is over
+        if let Some(token) = self.tokens.remove(&msg.id) {
+            if let Some(seat) = self.seats.get_mut(&token) {
+                let detached = Instant::now();
+                seat.detached = Some(detached);
+                ctx.run_later(RECONNECT_GRACE, move |act, ctx| {
+                    act.expire(token, detached, ctx.address())
+                });
+            }
+        }
+
+        for gid in left {
+            self.release(gid);
         }
     }
 }
 
+// Handler for Watch message.
+//
+// Register new spectator session of a public game (see ws/routes spectator_route)
+impl Handler<Watch> for GameServer {
+    type Result = Result<usize, APIError>;
+
+    fn handle(&mut self, msg: Watch, _: &mut Context<Self>) -> Self::Result {
+        let conn = self.pool.get()?;
+        if is_kicked(&conn, msg.gid, msg.uid)? {
+            return Err(APIError::AuthorizationError(
+                "You were kicked from this game".to_owned(),
+            ));
+        }
+
+        if !self.views.contains_key(&msg.gid) {
+            let state = match self.states.get(&msg.gid) {
+                Some(state) => *state,
+                None => GraphState::build_from_db(&conn, msg.gid)?,
+            };
+            self.load_view(&conn, msg.gid, state)?;
+        }
+
+        let id = self.session_id();
+        self.sessions.insert(id, msg.addr);
+        self.users.insert(id, msg.uid);
+        self.spectators.entry(msg.gid).or_default().insert(id);
+
+        Ok(id)
+    }
+}
+
+// Handler for ResumeMessage.
+impl Handler<ResumeMessage> for GameServer {
+    type Result = Option<Vec<Event>>;
+
+    fn handle(&mut self, msg: ResumeMessage, _: &mut Context<Self>) -> Self::Result {
+        // tokens only resume seats of the same user in the same game
+        let previous = match self.seats.get(&msg.token) {
+            Some(seat) if seat.uid == msg.uid && seat.gid == msg.gid => seat.session,
+            _ => return None,
+        };
+        let events = self.replay(msg.gid, msg.sequence)?;
+
+        // the earlier session may not have timed out yet. It keeps running without a seat
+        self.tokens.remove(&previous);
+        if let Some(fresh) = self.tokens.insert(msg.id, msg.token) {
+            self.seats.remove(&fresh);
+        }
+        if let Some(seat) = self.seats.get_mut(&msg.token) {
+            seat.session = msg.id;
+            seat.detached = None;
+        }
+
+        Some(events)
+    }
+}
+
 // handler for user move
 impl Handler<MakeMoveMessage> for GameServer {
     type Result = Result<bool, APIError>;
 
-    fn handle(&mut self, msg: MakeMoveMessage, _: &mut Context<Self>) -> Self::Result {
+    fn handle(&mut self, msg: MakeMoveMessage, ctx: &mut Context<Self>) -> Self::Result {
         // get connections
         let conn = self.pool.get()?;
-        let dest = [msg.action.0[3], msg.action.0[4], msg.action.0[5]];
-
-        // fetch starting point (db is trusted source)
-        let db_friendly_figure: i16 = msg.action.1.into(); // SMALLINT requires i16
-        let src = match fetch_latest_move(&conn, msg.gid, msg.uid, db_friendly_figure) {
-            // take response and translate to array
-            Ok((action, _)) => {
-                let (last_src, last_dest) = action.split_at(2);
-                // ensure move isn't repetitive
-                if dest == last_dest {
-                    return Err(APIError::ValidationError(
-                        "This move is repetitive".to_owned(),
-                    ));
-                } else {
-                    last_src
-                        .try_into()
-                        .expect("Mov fetching returned corrupted results")
+        let (figure, _, _) = rules::split(&msg.action);
+
+        // derive player id from seat order
+        let (seats, pid) = seat_of(&conn, msg.gid, msg.uid)?;
+
+        // only the player whose turn it is may move
+        let current = get_game_state(&conn, msg.gid)?;
+        match Turn::from_state(current) {
+            Some(Turn::Move(active)) if active == pid => (),
+            Some(Turn::Move(active)) | Some(Turn::Placement(active)) => {
+                return Err(RuleViolation::OutOfTurn(active).into());
+            }
+            _ => return Err(RuleViolation::NotRunning.into()),
+        };
+
+        // ensure move isn't the immediate reversal of the figures last move (Ko-rule)
+        let db_friendly_figure: i16 = figure.into(); // SMALLINT requires i16
+        match fetch_latest_move(&conn, msg.gid, msg.uid, db_friendly_figure) {
+            Ok(last) => {
+                if rules::is_reversal(&last, &msg.action) {
+                    return Err(rejected(&msg.action, RuleViolation::Repetition));
                 }
             }
-            // no move was made. Fall back
-            Err(DBError::NotFound { .. }) => [db_friendly_figure, 0, 0],
+            // no move was made yet
+            Err(DBError::NotFound { .. }) => (),
             Err(_) => {
                 eprintln!("Corrupted Database!!!!");
                 return Err(APIError::InternalError(
@@ -264,82 +1034,372 @@ impl Handler<MakeMoveMessage> for GameServer {
             }
         };
 
-        // validate move
-        let state = self.states.get(&msg.gid).unwrap();
-        let mut graph = GRAPH.clone();
-        graph.load_state(*state)?;
-        let result = graph.validate(&src, &dest)?;
-
-        match result.0 {
-            true => {
-                // add move to db
-                make_new_move(
-                    &conn,
-                    msg.uid,
-                    msg.gid,
-                    (
-                        [
-                            *src.get(0).unwrap(),
-                            *src.get(1).unwrap(),
-                            *src.get(2).unwrap(),
-                            dest[0],
-                            dest[1],
-                            dest[2],
-                        ],
-                        result.1,
-                    ),
-                )?;
-                // send message of move to all other players
+        // validate move against the current state
+        let state = match self.states.get(&msg.gid) {
+            Some(state) => state,
+            None => {
+                return Err(APIError::InternalError(
+                    "Game state wasn't loaded".to_owned(),
+                ));
+            }
+        };
+        let outcome =
+            rules::apply_move(state, pid, &msg.action).map_err(|why| rejected(&msg.action, why))?;
+
+        // the game is finished when a player brought enough figures home. The hash and
+        // games.state have to use the same turn (see graph/record replay)
+        let players = seats.len() as u8;
+        let next = rules::turn_after(&outcome.state, pid, players);
+        let hash = self.next_hash(
+            msg.gid,
+            &Position::new(*state, Turn::Move(pid)),
+            &Position::new(outcome.state, next),
+        );
 
-                Ok(true)
+        // the same position may only occur twice
+        if let Some(hashes) = self.hashes.get(&msg.gid) {
+            if zobrist::is_repetition(hashes, hash) {
+                return Err(rejected(&msg.action, RuleViolation::ThreefoldRepetition));
             }
-            false => Err(APIError::ValidationError(
-                "This move isn't possible".to_owned(),
+        }
+
+        // PG has no unsigned integers -> the bits are stored as they are
+        let mut moves = vec![(msg.uid, msg.action, Some(hash as i64))];
+        let ranking = match next {
+            Turn::Finished(_) => Some(rules::standings(&outcome.state, players)),
+            _ => None,
+        };
+        if let Some(ranking) = &ranking {
+            moves.extend(score_moves(&seats, ranking));
+        }
+
+        // add move (and the final ranking) to db and hand over the turn
+        commit_turn(&conn, msg.gid, &moves, current, next)?;
+        self.states.insert(msg.gid, outcome.state);
+        self.hashes.entry(msg.gid).or_default().push(hash);
+
+        // send message of move to all players
+        let event = GameEvent::MoveMade {
+            user: msg.uid,
+            action: MoveData::new(&msg.action),
+        };
+        self.broadcast(msg.gid, event);
+
+        match ranking {
+            Some(ranking) => self.announce_ranking(msg.gid, &seats, outcome.state, next, &ranking),
+            None => {
+                self.announce_turn(msg.gid, outcome.state, next);
+                if outcome.requires_placement() {
+                    self.request_placement(msg.gid, &msg.uid, &outcome.state);
+                }
+            }
+        }
+
+        self.schedule_bot(&conn, msg.gid, ctx.address());
+
+        Ok(true)
+    }
+}
+
+// handler for placing taken stoppers
+impl Handler<PlaceStopperMessage> for GameServer {
+    type Result = Result<bool, APIError>;
+
+    fn handle(&mut self, msg: PlaceStopperMessage, ctx: &mut Context<Self>) -> Self::Result {
+        let conn = self.pool.get()?;
+        let (seats, pid) = seat_of(&conn, msg.gid, msg.uid)?;
+
+        // only the player that took the stopper may place it
+        let current = get_game_state(&conn, msg.gid)?;
+        match Turn::from_state(current) {
+            Some(Turn::Placement(active)) if active == pid => (),
+            Some(Turn::Move(active)) | Some(Turn::Placement(active)) => {
+                return Err(RuleViolation::OutOfTurn(active).into());
+            }
+            _ => return Err(RuleViolation::NotRunning.into()),
+        };
+
+        let state = match self.states.get(&msg.gid) {
+            Some(state) => state,
+            None => {
+                return Err(APIError::InternalError(
+                    "Game state wasn't loaded".to_owned(),
+                ));
+            }
+        };
+
+        let figure = match msg.figure.or_else(|| rules::pending(state).first().copied()) {
+            Some(figure) => figure,
+            None => {
+                return Err(APIError::InternalError(
+                    "The game waits for a placement without a pending stopper".to_owned(),
+                ));
+            }
+        };
+
+        // validate placement against the current state
+        let action = rules::assemble(figure, HAND, msg.dest);
+        let next_state =
+            rules::apply_placement(state, &action).map_err(|why| rejected(&action, why))?;
+
+        let next = rules::turn_after(&next_state, pid, seats.len() as u8);
+        let hash = self.next_hash(
+            msg.gid,
+            &Position::new(*state, Turn::Placement(pid)),
+            &Position::new(next_state, next),
+        );
+
+        commit_turn(
+            &conn,
+            msg.gid,
+            &[(msg.uid, action, Some(hash as i64))],
+            current,
+            next,
+        )?;
+        self.states.insert(msg.gid, next_state);
+        self.hashes.entry(msg.gid).or_default().push(hash);
+
+        // notify room
+        let event = GameEvent::StopperPlaced {
+            user: msg.uid,
+            action: MoveData::new(&action),
+        };
+        self.broadcast(msg.gid, event);
+        self.announce_turn(msg.gid, next_state, next);
+
+        // gray and black stoppers may be pending at the same time
+        if let Turn::Placement(_) = next {
+            self.request_placement(msg.gid, &msg.uid, &next_state);
+        }
+
+        self.schedule_bot(&conn, msg.gid, ctx.address());
+
+        Ok(true)
+    }
+}
+
+// handler for computer players that have no acceptable move left (see give_up_turn)
+impl Handler<PassTurnMessage> for GameServer {
+    type Result = Result<(), APIError>;
+
+    fn handle(&mut self, msg: PassTurnMessage, ctx: &mut Context<Self>) -> Self::Result {
+        let conn = self.pool.get()?;
+
+        // humans can always leave the game instead. Absent players can't (see expire)
+        if !self.is_absent(msg.gid, msg.uid)
+            && !get_game_bots(&conn, msg.gid)?
+                .iter()
+                .any(|(uid, _)| *uid == msg.uid)
+        {
+            return Err(APIError::AuthorizationError(
+                "Only computer players can pass their turn".to_owned(),
+            ));
+        }
+
+        self.give_up_turn(&conn, msg.gid, msg.uid, ctx.address())
+    }
+}
+
+// handler for starting a game (host only)
+impl Handler<StartGameMessage> for GameServer {
+    type Result = Result<i16, APIError>;
+
+    fn handle(&mut self, msg: StartGameMessage, _: &mut Context<Self>) -> Self::Result {
+        let conn = self.pool.get()?;
+
+        // players may have joined after the state was loaded
+        let initial = GraphState::initial_from_db(&conn, msg.gid)?;
+        let variant = initial.variant();
+        if !variant.players().contains(&initial.players()) {
+            return Err(APIError::ValidationError(format!(
+                "A {} game requires {}",
+                variant,
+                variant.players_text()
+            )));
+        }
+
+        if !start_game(&conn, msg.gid, msg.uid)? {
+            return Err(APIError::AuthorizationError(
+                "Only the host can start a waiting game".to_owned(),
+            ));
+        }
+
+        let position = Position::new(initial, Turn::Move(1));
+        self.states.insert(msg.gid, initial);
+        self.hashes.insert(msg.gid, vec![zobrist::hash(&position)]);
+
+        let state = Turn::Move(1).to_state();
+        let event = GameEvent::GameStarted {
+            state,
+            position: position.to_string(),
+        };
+        self.broadcast(msg.gid, event);
+
+        Ok(state)
+    }
+}
+
+// handler for legal destination queries (used for highlighting reachable fields)
+impl Handler<LegalMovesMessage> for GameServer {
+    type Result = Result<Vec<FIELD>, APIError>;
+
+    fn handle(&mut self, msg: LegalMovesMessage, _: &mut Context<Self>) -> Self::Result {
+        match self.states.get(&msg.gid) {
+            Some(state) => Ok(movegen::destinations(state, msg.figure)?),
+            None => Err(APIError::InternalError(
+                "Game state wasn't loaded".to_owned(),
             )),
         }
     }
 }
 
+// handler for analysis requests. The search runs on the bot threads (see ws/bot)
+impl Handler<AnalysisMessage> for GameServer {
+    type Result = ResponseFuture<Result<(Position, Analysis), APIError>>;
+
+    fn handle(&mut self, msg: AnalysisMessage, _: &mut Context<Self>) -> Self::Result {
+        let request = self
+            .pool
+            .get()
+            .map_err(APIError::from)
+            .and_then(|conn| self.analysis(&conn, msg.gid));
+        let bots = self.bots.clone();
+
+        Box::pin(async move {
+            let request = request?;
+            let position = request.position;
+
+            match bots.send(request).await {
+                Ok(Ok(analysis)) => Ok((position, analysis)),
+                Ok(Err(why)) => Err(why.into()),
+                Err(why) => Err(APIError::IPCError(format!(
+                    "Couldn't deliver actor message: {}",
+                    why
+                ))),
+            }
+        })
+    }
+}
+
 // handler for game query message
 impl Handler<QueryGameMessage> for GameServer {
-    type Result = Result<(String, String, i32, Vec<(Uuid, String)>), APIError>;
+    type Result = Result<GameInfo, APIError>;
 
     fn handle(&mut self, msg: QueryGameMessage, _: &mut Context<Self>) -> Self::Result {
         let conn = self.pool.get()?;
 
-        let game = get_slim_game(&conn, msg.gid)?;
-        let users = get_game_users(&conn, msg.gid)?;
+        let (name, description, id) = get_slim_game(&conn, msg.gid)?;
+        let players = get_game_users(&conn, msg.gid)?;
 
-        match game.1 {
-            Some(desc) => Ok((game.0, desc, game.2, users)),
-            None => Ok((game.0, "".to_owned(), game.2, users)),
-        }
+        let state = match self.states.get(&msg.gid) {
+            Some(state) => *state,
+            None => GraphState::build_from_db(&conn, msg.gid)?,
+        };
+        let game_state = get_game_state(&conn, msg.gid)?;
+        let position = Position::from_game(state, game_state)?.to_string();
+        let sequence = self.sequences.get(&msg.gid).copied().unwrap_or(0);
+
+        // delayed spectators get the game they see
+        let (game_state, position, sequence) = match self.views.get(&msg.gid) {
+            Some(view) if msg.spectator => (view.state, view.position.clone(), view.sequence),
+            _ => (game_state, position, sequence),
+        };
+
+        Ok(GameInfo {
+            id,
+            name,
+            description: description.unwrap_or_default(),
+            state: game_state,
+            players,
+            position,
+            sequence,
+        })
     }
 }
 
-// Handler for ClientMessage message.
-impl Handler<ClientMessage> for GameServer {
+// handler for chat messages (see ws/chat)
+impl Handler<SendChatMessage> for GameServer {
     type Result = Result<(), APIError>;
 
-    fn handle(&mut self, msg: ClientMessage, _: &mut Context<Self>) -> Self::Result {
-        match msg.action {
-            0 => {
-                let conn = self.pool.get()?;
-                let (_, users) = get_game(&conn, msg.game)?;
+    fn handle(&mut self, msg: SendChatMessage, _: &mut Context<Self>) -> Self::Result {
+        let conn = self.pool.get()?;
+        if is_muted(&conn, msg.gid, msg.uid)? {
+            return Err(APIError::AuthorizationError(
+                "You were muted by the host".to_owned(),
+            ));
+        }
 
-                let mut data = HashMap::with_capacity(users.len());
-                users.iter().for_each(|(id, name)| {
-                    data.insert(id.to_string(), name.clone());
-                });
+        let text = chat::check(&self.filters, msg.message)?;
+        let message = create_chat_message(&conn, msg.gid, msg.uid, msg.channel.to_db(), &text)?;
 
-                Ok(self.sessions.get(&msg.id).unwrap().do_send(Message {
-                    action: msg.action,
-                    data,
-                })?)
+        // every channel has its own sessions
+        let sessions = match msg.channel {
+            Channel::Players => self.games.get(&msg.gid),
+            Channel::Spectators => self.spectators.get(&msg.gid),
+        };
+        if let Some(sessions) = sessions {
+            let message = ServerMessage::Chat(ChatEntry::new(message, msg.username, msg.channel));
+            for id in sessions {
+                if let Some(addr) = self.sessions.get(id) {
+                    let _ = addr.do_send(message.clone());
+                }
             }
-            1 => Ok(()),
-            _ => Ok(()),
         }
+
+        Ok(())
+    }
+}
+
+// handler for the chat history of a channel
+impl Handler<ChatHistoryMessage> for GameServer {
+    type Result = Result<Vec<ChatEntry>, APIError>;
+
+    fn handle(&mut self, msg: ChatHistoryMessage, _: &mut Context<Self>) -> Self::Result {
+        let conn = self.pool.get()?;
+
+        Ok(
+            get_chat_messages(&conn, msg.gid, msg.channel.to_db(), HISTORY_LENGTH)?
+                .into_iter()
+                .map(|(message, username)| ChatEntry::new(message, username, msg.channel))
+                .collect(),
+        )
+    }
+}
+
+// handler for muting users (host only)
+impl Handler<MuteMessage> for GameServer {
+    type Result = Result<(), APIError>;
+
+    fn handle(&mut self, msg: MuteMessage, _: &mut Context<Self>) -> Self::Result {
+        let conn = self.pool.get()?;
+        moderate(&conn, msg.gid, msg.uid, msg.user)?;
+
+        set_muted(&conn, msg.gid, msg.user, msg.muted)?;
+
+        let message = ServerMessage::Muted { muted: msg.muted };
+        self.send_user_message(&msg.gid, &msg.user, message);
+        Ok(())
+    }
+}
+
+// handler for kicking users (host only)
+impl Handler<KickMessage> for GameServer {
+    type Result = Result<(), APIError>;
+
+    fn handle(&mut self, msg: KickMessage, _: &mut Context<Self>) -> Self::Result {
+        let conn = self.pool.get()?;
+        moderate(&conn, msg.gid, msg.uid, msg.user)?;
+
+        // players may only be removed before the game started
+        if !kick_user(&conn, msg.gid, msg.user)? {
+            return Err(APIError::ValidationError(
+                "Players can't be kicked from running games".to_owned(),
+            ));
+        }
+
+        // the sessions of the user close themselves (see ws/session)
+        self.send_user_message(&msg.gid, &msg.user, ServerMessage::Kicked);
+        Ok(())
     }
 }
 
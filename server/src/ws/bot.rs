@@ -0,0 +1,137 @@
+use crate::graph::{
+    errors::GraphErr,
+    models::{HAND, MOVE},
+    notation::{format_move, Position},
+    rules,
+    search::{analyse, choose_move, fallback_move, Analysis, Difficulty},
+};
+use crate::ws::actor::{GameServer, MakeMoveMessage, PassTurnMessage, PlaceStopperMessage};
+use actix::prelude::*;
+use futures::executor::block_on;
+use uuid::Uuid;
+
+/*
+BotPlayer:
+    Computer players searching for their move (see graph/search) on their own threads so the
+    GameServer isn't blocked. The chosen move is sent back as MakeMoveMessage or
+    PlaceStopperMessage and validated exactly like the moves of humans. Bots without an accepted
+    move pass their turn (see PassTurnMessage)
+
+    Position analysis for players (see AnalysisMessage in ws/actor) runs on the same threads
+*/
+pub struct BotPlayer;
+
+// threads running the search
+pub const BOT_THREADS: usize = 2;
+
+impl Actor for BotPlayer {
+    type Context = SyncContext<Self>;
+}
+
+// it's the turn of a computer player
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct BotTurnMessage {
+    // user id of the computer player
+    pub uid: Uuid,
+    // related game id
+    pub gid: i32,
+    pub position: Position,
+    pub difficulty: Difficulty,
+    // last move of every figure of the player (Ko-rule)
+    pub last: Vec<MOVE>,
+    // zobrist hashes of all positions (see graph/zobrist)
+    pub history: Vec<u64>,
+    pub server: Addr<GameServer>,
+}
+
+// analyse a position (see graph/search analyse)
+#[derive(Message)]
+#[rtype(result = "Result<Analysis, GraphErr>")]
+pub struct AnalyseMessage {
+    pub position: Position,
+    // last move of every figure of the player whose turn it is (Ko-rule)
+    pub last: Vec<MOVE>,
+    // zobrist hashes of all positions (see graph/zobrist)
+    pub history: Vec<u64>,
+}
+
+impl Handler<BotTurnMessage> for BotPlayer {
+    type Result = ();
+
+    fn handle(&mut self, msg: BotTurnMessage, _: &mut SyncContext<Self>) {
+        let action = choose_move(
+            &msg.position,
+            msg.difficulty,
+            &msg.last,
+            &msg.history,
+            &mut rand::thread_rng(),
+        )
+        .and_then(|action| match action {
+            Some(action) => Ok(Some(action)),
+            // every move was filtered -> any legal move is better than a stuck game
+            None => fallback_move(&msg.position, &msg.last),
+        });
+
+        let action = match action {
+            Ok(Some(action)) => action,
+            Ok(None) => return pass(&msg),
+            Err(why) => {
+                eprintln!("GraphErr: {}", why);
+                return pass(&msg);
+            }
+        };
+
+        let (figure, src, dest) = rules::split(&action);
+        let result = if src == HAND {
+            block_on(msg.server.send(PlaceStopperMessage {
+                uid: msg.uid,
+                figure: Some(figure),
+                dest,
+                gid: msg.gid,
+            }))
+        } else {
+            block_on(msg.server.send(MakeMoveMessage {
+                uid: msg.uid,
+                action,
+                gid: msg.gid,
+            }))
+        };
+
+        match result {
+            Ok(Ok(_)) => (),
+            Ok(Err(why)) => {
+                eprintln!(
+                    "Bot move {} in game {} was rejected: {}",
+                    format_move(&action),
+                    msg.gid,
+                    why
+                );
+                pass(&msg);
+            }
+            Err(why) => eprintln!("GameServer didn't receive bot move: {}", why),
+        }
+    }
+}
+
+// give up the turn so the game doesn't wait for the bot forever (see PassTurnMessage in ws/actor)
+fn pass(msg: &BotTurnMessage) {
+    let result = block_on(msg.server.send(PassTurnMessage {
+        uid: msg.uid,
+        gid: msg.gid,
+    }));
+
+    match result {
+        Ok(Ok(_)) => (),
+        Ok(Err(why)) => eprintln!("Bot of game {} couldn't pass: {}", msg.gid, why),
+        Err(why) => eprintln!("GameServer didn't receive bot pass: {}", why),
+    }
+}
+
+impl Handler<AnalyseMessage> for BotPlayer {
+    type Result = Result<Analysis, GraphErr>;
+
+    fn handle(&mut self, msg: AnalyseMessage, _: &mut SyncContext<Self>) -> Self::Result {
+        analyse(&msg.position, &msg.last, &msg.history)
+    }
+}
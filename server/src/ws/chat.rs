@@ -0,0 +1,213 @@
+use super::errors::ChatError;
+use crate::config::ServerConfig;
+use hashbrown::HashSet;
+use serde::{Deserialize, Serialize};
+use std::fs::read_to_string;
+use std::io::Error;
+use std::path::Path;
+use std::process::exit;
+
+/*
+Chat:
+    Every game has a chat for its players and one for its spectators (see Channel). Messages pass
+    the filters of the GameServer (see ws/actor SendChatMessage) before they're stored
+    (see db/model ChatMessages) and sent to the sessions of the channel
+
+    The host of a game may mute users in both channels and kick spectators and the players of a
+    waiting game. Both are stored (see db/model GameModerations), kicked users can't join, rejoin
+    or watch the game again
+*/
+
+// longest chat message in characters
+pub const MAX_MESSAGE_LENGTH: usize = 500;
+// messages sent as reply to chat_history (see ws/protocol)
+pub const HISTORY_LENGTH: i64 = 100;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    Players,
+    Spectators,
+}
+
+impl Channel {
+    // channel of a session (see ws/session spectator)
+    pub fn of(spectator: bool) -> Channel {
+        if spectator {
+            Channel::Spectators
+        } else {
+            Channel::Players
+        }
+    }
+
+    // see db/model ChatMessages
+    pub fn to_db(self) -> i16 {
+        match self {
+            Channel::Players => 0,
+            Channel::Spectators => 1,
+        }
+    }
+}
+
+/*
+ChatFilter:
+    Checks chat messages before they're broadcast. Filters run in order and get the message
+    returned by the previous one, so they may reject a message or rewrite it
+*/
+pub trait ChatFilter: Send {
+    fn check(&self, message: String) -> Result<String, ChatError>;
+}
+
+// trims messages and rejects empty and too long ones
+pub struct LengthFilter {
+    pub max: usize,
+}
+
+impl ChatFilter for LengthFilter {
+    fn check(&self, message: String) -> Result<String, ChatError> {
+        let message = message.trim();
+
+        if message.is_empty() {
+            Err(ChatError::Empty)
+        } else if message.chars().count() > self.max {
+            Err(ChatError::TooLong(self.max))
+        } else {
+            Ok(message.to_owned())
+        }
+    }
+}
+
+// masks blocked words with * regardless of their case
+pub struct WordFilter {
+    words: HashSet<String>,
+}
+
+impl WordFilter {
+    pub fn new<I: IntoIterator<Item = String>>(words: I) -> WordFilter {
+        WordFilter {
+            words: words.into_iter().map(|word| word.to_lowercase()).collect(),
+        }
+    }
+
+    // one word per line. Empty lines and lines starting with # are skipped
+    pub fn load(path: &Path) -> Result<WordFilter, Error> {
+        let words = read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_owned)
+            .collect::<Vec<String>>();
+
+        Ok(WordFilter::new(words))
+    }
+
+    fn push_word(&self, filtered: &mut String, word: &str) {
+        if self.words.contains(&word.to_lowercase()) {
+            filtered.extend(word.chars().map(|_| '*'));
+        } else {
+            filtered.push_str(word);
+        }
+    }
+}
+
+impl ChatFilter for WordFilter {
+    fn check(&self, message: String) -> Result<String, ChatError> {
+        let mut filtered = String::with_capacity(message.len());
+        let mut word = String::new();
+
+        for character in message.chars() {
+            if character.is_alphanumeric() {
+                word.push(character);
+            } else {
+                self.push_word(&mut filtered, &word);
+                word.clear();
+                filtered.push(character);
+            }
+        }
+        self.push_word(&mut filtered, &word);
+
+        Ok(filtered)
+    }
+}
+
+// filters of the configured server (see ServerConfig chat_filter)
+pub fn filters(config: &ServerConfig) -> Vec<Box<dyn ChatFilter>> {
+    let mut filters: Vec<Box<dyn ChatFilter>> = vec![Box::new(LengthFilter {
+        max: MAX_MESSAGE_LENGTH,
+    })];
+
+    if let Some(path) = &config.chat_filter {
+        match WordFilter::load(Path::new(path)) {
+            Ok(filter) => filters.push(Box::new(filter)),
+            Err(why) => {
+                eprintln!("ERROR: unable to load chat filter '{}': {}", path, why);
+                exit(1)
+            }
+        }
+    }
+
+    filters
+}
+
+pub fn check(filters: &[Box<dyn ChatFilter>], message: String) -> Result<String, ChatError> {
+    filters
+        .iter()
+        .try_fold(message, |message, filter| filter.check(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocked() -> WordFilter {
+        WordFilter::new(vec!["Darn".to_owned(), "heck".to_owned()])
+    }
+
+    #[test]
+    fn length_filter() {
+        let filter = LengthFilter { max: 5 };
+
+        assert_eq!(
+            filter.check("  hello \n".to_owned()),
+            Ok("hello".to_owned())
+        );
+        assert_eq!(filter.check(" \t ".to_owned()), Err(ChatError::Empty));
+        assert_eq!(
+            filter.check("hello!".to_owned()),
+            Err(ChatError::TooLong(5))
+        );
+        // characters, not bytes
+        assert_eq!(filter.check("äöüßé".to_owned()), Ok("äöüßé".to_owned()));
+    }
+
+    #[test]
+    fn word_filter() {
+        let filter = blocked();
+
+        assert_eq!(
+            filter.check("Oh DARN, what the heck!".to_owned()),
+            Ok("Oh ****, what the ****!".to_owned())
+        );
+        // only whole words are masked
+        assert_eq!(
+            filter.check("darnation heckle".to_owned()),
+            Ok("darnation heckle".to_owned())
+        );
+    }
+
+    #[test]
+    fn filters_run_in_order() {
+        let filters: Vec<Box<dyn ChatFilter>> =
+            vec![Box::new(LengthFilter { max: 10 }), Box::new(blocked())];
+
+        assert_eq!(
+            check(&filters, "  heck  ".to_owned()),
+            Ok("****".to_owned())
+        );
+        assert_eq!(check(&filters, "   ".to_owned()), Err(ChatError::Empty));
+        assert_eq!(
+            check(&filters, "heck heck heck".to_owned()),
+            Err(ChatError::TooLong(10))
+        );
+    }
+}
@@ -1,21 +1,22 @@
+use super::protocol::{ServerMessage, PROTOCOL_VERSION};
+use crate::api::errors::APIError;
+use crate::graph::errors::NotationError;
 use derive_more::{Display, Error};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 /*
-UserError:
-    displays user when something is not e.g. available as html for GET Requests
-    returns template error.html
+WebsocketError:
+    Errors of the WebSocket sessions. They are sent to the client as ErrorMessage
+    (see ws/protocol ServerMessage::Error) and the session stays open unless noted otherwise
 
     Errors:
-    ValidationError: Only returned for non-auth queries as everything else is API (POST) based
     InternalError: Something went really, really wrong
-
-    codes:
-        1: not found
-        2: LOL
+    MessageFormatError: The message isn't a ClientMessage (see ws/protocol)
+    VersionError: The client speaks another protocol version. The session is closed
+    HandshakeError: The client didn't send hello yet
+    ValidationError: The request was rejected (e.g. an illegal move)
+    AuthorizationError: The user isn't allowed to do this (e.g. starting a game they don't host)
 */
-// pub type WebResult<R> = Result<R, WebsocketError>;
-
 #[derive(Debug, Error, Display)]
 pub enum WebsocketError {
     #[display(fmt = "Internal Error")]
@@ -24,50 +25,121 @@ pub enum WebsocketError {
     MessageFormatError {},
     #[display(fmt = "Unimplemented feature")]
     UnimplementedError {},
+    #[display(fmt = "Unsupported protocol version {}", version)]
+    VersionError { version: u16 },
+    #[display(fmt = "Handshake required")]
+    HandshakeError {},
+    #[display(fmt = "{}", message)]
+    ValidationError { message: String },
+    #[display(fmt = "{}", message)]
+    AuthorizationError { message: String },
+}
+
+/*
+ChatError:
+    Chat messages rejected by a filter (see ws/chat ChatFilter)
+*/
+#[derive(Debug, Display, PartialEq)]
+pub enum ChatError {
+    #[display(fmt = "Empty chat message")]
+    Empty,
+    #[display(fmt = "Chat messages can't be longer than {} characters", _0)]
+    TooLong(usize),
 }
 
-#[derive(Serialize)]
-pub struct ErrorMessage<'a> {
-    /*
-    This represents
+// stable identifier of the error for clients
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    Internal,
+    MessageFormat,
+    Unimplemented,
+    UnsupportedVersion,
+    HandshakeRequired,
+    Validation,
+    Unauthorized,
+}
 
-    0: InternalError
-    1: MessageformatError
-    u8::MAX: UnimplementedError
-    */
-    code: u8,
-    message: &'a str,
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorMessage {
+    pub code: ErrorCode,
+    pub message: String,
 }
 
-impl<'a> ErrorMessage<'a> {
-    pub fn text<'x>(error: WebsocketError) -> String {
+impl ErrorMessage {
+    pub fn new(error: WebsocketError) -> ErrorMessage {
         let (code, message) = match error {
-            WebsocketError::InternalError { .. } => {
-                (0, "Internal Error: Sorry for any caused inconvenience")
-            }
-            WebsocketError::MessageFormatError { .. } => {
-                (1, "MessageError: Seems like your message couldn't be loaded from JSON")
-            }
-            WebsocketError::UnimplementedError { .. } => {
-                (u8::MAX, "Unimplemented: The action you tried to use is either implemented/ supported at them moment")
-            }
+            WebsocketError::InternalError { .. } => (
+                ErrorCode::Internal,
+                "Internal Error: Sorry for any caused inconvenience".to_owned(),
+            ),
+            WebsocketError::MessageFormatError { .. } => (
+                ErrorCode::MessageFormat,
+                "MessageError: Seems like your message couldn't be loaded from JSON".to_owned(),
+            ),
+            WebsocketError::UnimplementedError { .. } => (
+                ErrorCode::Unimplemented,
+                "Unimplemented: The action you tried to use isn't supported at the moment"
+                    .to_owned(),
+            ),
+            WebsocketError::VersionError { version } => (
+                ErrorCode::UnsupportedVersion,
+                format!(
+                    "Unsupported protocol version {}. The server speaks version {}",
+                    version, PROTOCOL_VERSION
+                ),
+            ),
+            WebsocketError::HandshakeError { .. } => (
+                ErrorCode::HandshakeRequired,
+                "Handshake required: Send hello with your protocol version first".to_owned(),
+            ),
+            WebsocketError::ValidationError { message } => (ErrorCode::Validation, message),
+            WebsocketError::AuthorizationError { message } => (ErrorCode::Unauthorized, message),
         };
 
-        return serde_json::to_string(&ErrorMessage { code, message })
-            .expect("The creation of websocket error messages failed");
+        ErrorMessage { code, message }
+    }
+
+    // error as sent to the client
+    pub fn text(error: WebsocketError) -> String {
+        serde_json::to_string(&ServerMessage::Error(ErrorMessage::new(error)))
+            .expect("The creation of websocket error messages failed")
     }
 }
 
 impl From<serde_json::Error> for WebsocketError {
     fn from(_: serde_json::Error) -> Self {
-        return WebsocketError::MessageFormatError {};
+        WebsocketError::MessageFormatError {}
     }
 }
 
 impl From<diesel::result::Error> for WebsocketError {
     fn from(why: diesel::result::Error) -> Self {
         eprintln!("Diesel Execution failed: {:?}", why);
-        return WebsocketError::InternalError {};
+        WebsocketError::InternalError {}
+    }
+}
+
+// Moves and fields are sent by clients -> invalid notation is a validation error
+impl From<NotationError> for WebsocketError {
+    fn from(error: NotationError) -> Self {
+        WebsocketError::ValidationError {
+            message: error.to_string(),
+        }
+    }
+}
+
+// Errors of the GameServer (see ws/actor). Internal details aren't sent to the client
+impl From<APIError> for WebsocketError {
+    fn from(error: APIError) -> Self {
+        match error {
+            APIError::ValidationError(message) => WebsocketError::ValidationError { message },
+            APIError::AuthorizationError(message) => WebsocketError::AuthorizationError { message },
+            why => {
+                eprintln!("GameServer failed: {}", why);
+                WebsocketError::InternalError {}
+            }
+        }
     }
 }
 
@@ -79,4 +151,5 @@ lazy_static! {
         ErrorMessage::text(WebsocketError::MessageFormatError {});
     pub static ref UNIMPLEMENTED_ERROR: String =
         ErrorMessage::text(WebsocketError::UnimplementedError {});
+    pub static ref HANDSHAKE_ERROR: String = ErrorMessage::text(WebsocketError::HandshakeError {});
 }
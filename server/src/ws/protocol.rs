@@ -0,0 +1,509 @@
+use super::chat::Channel;
+use super::errors::ErrorMessage;
+use crate::api::responses::GetGameAnalysisResponse;
+use crate::db::model::ChatMessage;
+use crate::graph::{
+    errors::NotationError,
+    graph::Figure,
+    models::{FIELD, MOVE},
+    notation::{format_move, parse_field, parse_move},
+    rules,
+};
+use actix::prelude::*;
+use chrono::{TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/*
+WebSocket protocol:
+    Every message is a JSON object tagged with its type. The client has to start with hello and
+    the protocol version it speaks. The server answers with welcome or closes the connection after
+    an error with the code unsupported_version. Other messages before the handshake are answered
+    with the error handshake_required
+
+    -> {"type": "hello", "version": 2}
+    <- {"type": "welcome", "version": 2, "game": 42, "token": "...", "resumed": false}
+    -> {"type": "make_move", "move": "1:f-a"}
+    <- {"type": "move_accepted", "move": {"figure": 1, "src": [5, 0, 0], "dest": [0, 0, 0], "notation": "1:f-a"}}
+
+    Requests that fail are answered with an error (see ws/errors ErrorMessage):
+    <- {"type": "error", "code": "validation", "message": "..."}
+
+    Changes of a game are pushed to all of its sessions as events (see Event)
+
+    welcome contains a reconnect token. After losing the connection the client may resume its
+    session by sending the token and the sequence of the last event it received with hello. The
+    server then replays the missed events right after welcome. Tokens expire when the client
+    doesn't reconnect within the grace period (see ws/actor RECONNECT_GRACE). If the session can't
+    be resumed welcome contains resumed: false and the client has to query the game again
+
+    -> {"type": "hello", "version": 2, "resume": {"token": "...", "sequence": 7}}
+    <- {"type": "welcome", "version": 2, "game": 42, "token": "...", "resumed": true}
+    <- {"type": "event", "sequence": 8, ...}
+
+    Spectators of public games (see ws/routes spectator_route) use the same protocol. They receive
+    all events, possibly delayed (see ws/actor), and may only query the game and chat. Their other
+    requests would change the game or reveal the live position and are rejected with the error
+    unauthorized. Their sessions can't be resumed
+
+    Changes that break existing clients increase PROTOCOL_VERSION
+*/
+pub const PROTOCOL_VERSION: u16 = 2;
+
+/*
+Messages of the client (see ws/session)
+
+| type          | fields                                      | reply         | host only |
+| ------------- | ------------------------------------------- | ------------- | --------- |
+| hello         | version: u16, resume: Resume (optional)     | welcome       | no        |
+| query_game    |                                             | game          | no        |
+| make_move     | move: MoveInput                             | move_accepted | no        |
+| place_stopper | dest: FieldInput, figure: u8 (optional)     | -             | no        |
+| start_game    |                                             | -             | yes       |
+| legal_moves   | figure: u8                                  | legal_moves   | no        |
+| analysis      |                                             | analysis      | no        |
+| chat          | message: String                             | -             | no        |
+| chat_history  |                                             | chat_history  | no        |
+| mute          | user: Uuid                                  | -             | yes       |
+| unmute        | user: Uuid                                  | -             | yes       |
+| kick          | user: Uuid                                  | -             | yes       |
+
+Placed stoppers and started games are announced to the whole game as events (see Event). The figure
+of place_stopper defaults to the first stopper waiting to be placed
+
+Analysis is only available for finished games and practice games (one human against computer players)
+
+Chat messages are sent as chat to every session of the channel of the sender (see ws/chat), which
+includes the sender. chat_history returns the last messages of the channel. Muted users get muted
+and kicked users kicked before their session is closed
+*/
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Hello {
+        version: u16,
+        #[serde(default)]
+        resume: Option<Resume>,
+    },
+    QueryGame,
+    MakeMove {
+        #[serde(rename = "move")]
+        action: MoveInput,
+    },
+    PlaceStopper {
+        dest: FieldInput,
+        #[serde(default)]
+        figure: Option<Figure>,
+    },
+    StartGame,
+    LegalMoves {
+        figure: Figure,
+    },
+    Analysis,
+    Chat {
+        message: String,
+    },
+    ChatHistory,
+    Mute {
+        user: Uuid,
+    },
+    Unmute {
+        user: Uuid,
+    },
+    Kick {
+        user: Uuid,
+    },
+}
+
+// Messages of the server: the replies listed at ClientMessage and the events of the game
+
+#[derive(Message, Serialize, Debug, Clone)]
+#[rtype(result = "()")]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Welcome {
+        version: u16,
+        game: i32,
+        // reconnect token of the session
+        token: Uuid,
+        // whether an earlier session was resumed
+        resumed: bool,
+    },
+    Error(ErrorMessage),
+    Game(GameInfo),
+    MoveAccepted {
+        #[serde(rename = "move")]
+        action: MoveData,
+    },
+    LegalMoves {
+        figure: Figure,
+        destinations: Vec<FIELD>,
+    },
+    Analysis(GetGameAnalysisResponse),
+    Event(Event),
+    Chat(ChatEntry),
+    ChatHistory {
+        messages: Vec<ChatEntry>,
+    },
+    Muted {
+        muted: bool,
+    },
+    Kicked,
+}
+
+/*
+Event:
+    Change of a game pushed by the GameServer (see ws/actor broadcast) to every session of the game
+    that finished the handshake. The sequence increases by one with every event of a game, so
+    clients notice missed events and can query the game again. query_game returns the sequence of
+    the last event the reply includes. Sequences start at 1 whenever the server (re)starts
+
+    <- {"type": "event", "sequence": 7, "event": "move_made", "user": "...", "move": {...}}
+
+| event              | fields                                        |
+| ------------------ | --------------------------------------------- |
+| game_started       | state: i16, position: String                  |
+| move_made          | user: Uuid, move: MoveData                    |
+| stopper_placed     | user: Uuid, move: MoveData                    |
+| placement_required | user: Uuid, figures: [u8]                     |
+| turn_changed       | state: i16, position: String                  |
+| game_finished      | state: i16, position: String, ranking: [Rank] |
+| disconnected       | user: Uuid                                    |
+
+Every accepted move or placement is followed by turn_changed or game_finished. state is the state
+of the game (see db/model Game) and position the position after the change (see graph/notation)
+
+After disconnected the turns of the user are passed for them until they connect again. If they
+can't pass or have to place stoppers, the game ends with the current points (see ws/actor
+give_up_turn). Games nobody is connected to are unloaded and their events dropped
+*/
+#[derive(Serialize, Debug, Clone)]
+pub struct Event {
+    pub sequence: u64,
+    #[serde(flatten)]
+    pub event: GameEvent,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum GameEvent {
+    GameStarted {
+        state: i16,
+        position: String,
+    },
+    MoveMade {
+        user: Uuid,
+        #[serde(rename = "move")]
+        action: MoveData,
+    },
+    StopperPlaced {
+        user: Uuid,
+        #[serde(rename = "move")]
+        action: MoveData,
+    },
+    PlacementRequired {
+        user: Uuid,
+        figures: Vec<Figure>,
+    },
+    TurnChanged {
+        state: i16,
+        position: String,
+    },
+    GameFinished {
+        state: i16,
+        position: String,
+        ranking: Vec<Rank>,
+    },
+    Disconnected {
+        user: Uuid,
+    },
+}
+
+// earlier session to resume (see PROTOCOL_VERSION)
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Resume {
+    pub token: Uuid,
+    // last event the client received
+    pub sequence: u64,
+}
+
+// move in the notation (see graph/notation) or as figure and fields
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum MoveInput {
+    Notation(String),
+    Fields {
+        figure: Figure,
+        src: FIELD,
+        dest: FIELD,
+    },
+}
+
+// field in the notation (e.g. "ag3") or as [i16; 3]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum FieldInput {
+    Notation(String),
+    Field(FIELD),
+}
+
+// move as sent by the server
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct MoveData {
+    pub figure: Figure,
+    pub src: FIELD,
+    pub dest: FIELD,
+    pub notation: String,
+}
+
+// metadata and current position of a game (reply to query_game)
+#[derive(Serialize, Debug, Clone)]
+pub struct GameInfo {
+    pub id: i32,
+    pub name: String,
+    pub description: String,
+    // see db/model Game
+    pub state: i16,
+    pub players: Vec<(Uuid, String)>,
+    // see graph/notation
+    pub position: String,
+    // last event included in the info (see Event)
+    pub sequence: u64,
+}
+
+// chat message as sent to the clients (see ws/chat)
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChatEntry {
+    pub id: i32,
+    pub user: Uuid,
+    pub username: String,
+    pub channel: Channel,
+    pub message: String,
+    // seconds since the epoch
+    pub sent: i64,
+}
+
+// final result of a player (see graph/rules ranking)
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Rank {
+    pub user: Uuid,
+    pub points: u8,
+    pub rank: u8,
+}
+
+impl MoveInput {
+    pub fn resolve(&self) -> Result<MOVE, NotationError> {
+        match self {
+            MoveInput::Notation(raw) => parse_move(raw),
+            MoveInput::Fields { figure, src, dest } => Ok(rules::assemble(*figure, *src, *dest)),
+        }
+    }
+}
+
+impl FieldInput {
+    pub fn resolve(&self) -> Result<FIELD, NotationError> {
+        match self {
+            FieldInput::Notation(raw) => parse_field(raw),
+            FieldInput::Field(field) => Ok(*field),
+        }
+    }
+}
+
+impl ChatEntry {
+    pub fn new(message: ChatMessage, username: String, channel: Channel) -> ChatEntry {
+        ChatEntry {
+            id: message.id,
+            user: message.user_id,
+            username,
+            channel,
+            message: message.message,
+            sent: Utc.from_utc_datetime(&message.created_at).timestamp(),
+        }
+    }
+}
+
+impl MoveData {
+    pub fn new(action: &MOVE) -> MoveData {
+        let (figure, src, dest) = rules::split(action);
+        MoveData {
+            figure,
+            src,
+            dest,
+            notation: format_move(action),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ws::errors::WebsocketError;
+    use serde_json::{from_str, json, to_value};
+
+    #[test]
+    fn client_messages() {
+        let parse = |raw: &str| from_str::<ClientMessage>(raw).unwrap();
+
+        assert_eq!(
+            parse(r#"{"type": "hello", "version": 1}"#),
+            ClientMessage::Hello {
+                version: 1,
+                resume: None
+            }
+        );
+        assert_eq!(
+            parse(
+                r#"{"type": "hello", "version": 2, "resume": {"token": "00000000-0000-0000-0000-000000000000", "sequence": 7}}"#
+            ),
+            ClientMessage::Hello {
+                version: 2,
+                resume: Some(Resume {
+                    token: Uuid::nil(),
+                    sequence: 7
+                })
+            }
+        );
+        assert_eq!(parse(r#"{"type": "query_game"}"#), ClientMessage::QueryGame);
+
+        let notation = parse(r#"{"type": "make_move", "move": "1:f-a"}"#);
+        let fields = parse(
+            r#"{"type": "make_move", "move": {"figure": 1, "src": [5, 0, 0], "dest": [0, 0, 0]}}"#,
+        );
+        for message in &[notation, fields] {
+            match message {
+                ClientMessage::MakeMove { action } => {
+                    assert_eq!(action.resolve(), Ok(([5, 0, 0, 0, 0, 0], 1)))
+                }
+                _ => panic!("Expected make_move, got {:?}", message),
+            }
+        }
+
+        assert_eq!(
+            parse(r#"{"type": "place_stopper", "dest": [0, 3, 5]}"#),
+            ClientMessage::PlaceStopper {
+                dest: FieldInput::Field([0, 3, 5]),
+                figure: None
+            }
+        );
+        assert_eq!(
+            parse(r#"{"type": "place_stopper", "dest": "ag3", "figure": 31}"#),
+            ClientMessage::PlaceStopper {
+                dest: FieldInput::Notation("ag3".to_owned()),
+                figure: Some(31)
+            }
+        );
+        assert_eq!(
+            parse(r#"{"type": "chat", "message": "gg"}"#),
+            ClientMessage::Chat {
+                message: "gg".to_owned()
+            }
+        );
+        assert_eq!(
+            parse(r#"{"type": "kick", "user": "00000000-0000-0000-0000-000000000000"}"#),
+            ClientMessage::Kick { user: Uuid::nil() }
+        );
+    }
+
+    #[test]
+    fn malformed_client_messages() {
+        for raw in &[
+            r#"{"action": 2, "data": {}}"#,
+            r#"{"type": "hello"}"#,
+            r#"{"type": "hello", "version": -1}"#,
+            r#"{"type": "hello", "version": 2, "resume": {"token": "abc", "sequence": 7}}"#,
+            r#"{"type": "legal_moves", "figure": 256}"#,
+            r#"{"type": "make_move", "move": [5, 0, 0, 0, 0, 0]}"#,
+            r#"{"type": "resign"}"#,
+        ] {
+            assert!(
+                from_str::<ClientMessage>(raw).is_err(),
+                "{} was accepted",
+                raw
+            );
+        }
+    }
+
+    #[test]
+    fn server_messages() {
+        assert_eq!(
+            to_value(ServerMessage::Welcome {
+                version: PROTOCOL_VERSION,
+                game: 42,
+                token: Uuid::nil(),
+                resumed: false
+            })
+            .unwrap(),
+            json!({
+                "type": "welcome",
+                "version": PROTOCOL_VERSION,
+                "game": 42,
+                "token": Uuid::nil(),
+                "resumed": false
+            })
+        );
+        assert_eq!(
+            to_value(ServerMessage::MoveAccepted {
+                action: MoveData::new(&([5, 0, 0, 0, 0, 0], 1))
+            })
+            .unwrap(),
+            json!({
+                "type": "move_accepted",
+                "move": {"figure": 1, "src": [5, 0, 0], "dest": [0, 0, 0], "notation": "1:f-a"}
+            })
+        );
+        assert_eq!(
+            to_value(ServerMessage::Error(ErrorMessage::new(
+                WebsocketError::VersionError { version: 7 }
+            )))
+            .unwrap()["code"],
+            json!("unsupported_version")
+        );
+        assert_eq!(
+            to_value(ServerMessage::Chat(ChatEntry {
+                id: 3,
+                user: Uuid::nil(),
+                username: "jan".to_owned(),
+                channel: Channel::Spectators,
+                message: "gg".to_owned(),
+                sent: 1_611_964_800,
+            }))
+            .unwrap(),
+            json!({
+                "type": "chat",
+                "id": 3,
+                "user": Uuid::nil(),
+                "username": "jan",
+                "channel": "spectators",
+                "message": "gg",
+                "sent": 1_611_964_800
+            })
+        );
+        assert_eq!(
+            to_value(ServerMessage::Kicked).unwrap(),
+            json!({"type": "kicked"})
+        );
+    }
+
+    #[test]
+    fn events() {
+        let user = Uuid::nil();
+        let event = ServerMessage::Event(Event {
+            sequence: 7,
+            event: GameEvent::MoveMade {
+                user,
+                action: MoveData::new(&([5, 0, 0, 0, 0, 0], 1)),
+            },
+        });
+
+        assert_eq!(
+            to_value(event).unwrap(),
+            json!({
+                "type": "event",
+                "sequence": 7,
+                "event": "move_made",
+                "user": user,
+                "move": {"figure": 1, "src": [5, 0, 0], "dest": [0, 0, 0], "notation": "1:f-a"}
+            })
+        );
+    }
+}
@@ -1,13 +1,15 @@
 use crate::api::errors::APIError;
 use crate::auth::{guard_api_with_user, guard_with_user};
-use crate::db::actions::{create_toast, get_user_game, leave_game};
+use crate::db::actions::{create_toast, get_game, get_user_game, is_kicked, leave_game};
 use crate::db::model::SlimUser;
+use crate::frontend::errors::UserError;
 use crate::frontend::routes::{redirect, DbPool, UserResponse};
 use crate::ws::{actor::GameServer, session::WsGameSession};
 use actix::prelude::*;
-use actix_web::{web::block, web::Data, web::HttpResponse, web::Payload, HttpRequest};
+use actix_web::{web::block, web::Data, web::HttpResponse, web::Path, web::Payload, HttpRequest};
 use actix_web_actors::ws;
 use std::time::Instant;
+use uuid::Uuid;
 
 pub async fn game_route(
     req: HttpRequest,
@@ -35,6 +37,59 @@ pub async fn game_route(
         }
     };
 
+    // get_user_game is cached -> kicked users may still show up as players for a moment
+    let conn = pool.get()?;
+    let uid = user.id;
+    if block(move || is_kicked(&conn, gid, uid)).await? {
+        return Err(APIError::AuthorizationError(
+            "You were kicked from this game".to_owned(),
+        ));
+    }
+
+    Ok(ws::start(
+        WsGameSession {
+            id: 0,
+            uid: user,
+            game: gid,
+            hb: Instant::now(),
+            addr: srv.get_ref().clone(),
+            version: None,
+            token: Uuid::nil(),
+            spectator: false,
+        },
+        &req,
+        stream,
+    )?)
+}
+
+// read-only session of a public game. Users don't need to join the game
+pub async fn spectator_route(
+    req: HttpRequest,
+    stream: Payload,
+    srv: Data<Addr<GameServer>>,
+    pool: Data<DbPool>,
+    path: Path<i32>,
+    id: Option<SlimUser>,
+) -> Result<HttpResponse, APIError> {
+    let user = guard_api_with_user(id)?;
+    let conn = pool.get()?;
+
+    let gid = path.into_inner();
+    let (game, _) = block(move || get_game(&conn, gid)).await?;
+    if !game.public {
+        return Err(APIError::AuthorizationError(
+            "Only public games can be watched".to_owned(),
+        ));
+    }
+
+    let conn = pool.get()?;
+    let uid = user.id;
+    if block(move || is_kicked(&conn, gid, uid)).await? {
+        return Err(APIError::AuthorizationError(
+            "You were kicked from this game".to_owned(),
+        ));
+    }
+
     Ok(ws::start(
         WsGameSession {
             id: 0,
@@ -42,6 +97,9 @@ pub async fn game_route(
             game: gid,
             hb: Instant::now(),
             addr: srv.get_ref().clone(),
+            version: None,
+            token: Uuid::nil(),
+            spectator: true,
         },
         &req,
         stream,
@@ -55,7 +113,12 @@ pub async fn get_game_leave_route(id: Option<SlimUser>, pool: Data<DbPool>) -> U
     let conn = pool.get()?;
     let cloned_id = user.id.clone();
 
-    block(move || leave_game(&conn, cloned_id)).await?;
+    // players of running games keep their seat (see db/actions leave_game)
+    if !block(move || leave_game(&conn, cloned_id)).await? {
+        return Err(UserError::AuthorizationError(
+            "You can't leave a game that already started".to_owned(),
+        ));
+    }
 
     // alert user
     let conn = pool.get()?;
@@ -1,11 +1,20 @@
-use super::actor::{Connect, Disconnect, GameServer, MakeMoveMessage, Message, QueryGameMessage};
-use super::errors::{MESSAGE_FORMAT_ERROR, UNIMPLEMENTED_ERROR};
+use super::actor::{
+    AnalysisMessage, ChatHistoryMessage, Connect, Disconnect, GameServer, KickMessage,
+    LegalMovesMessage, MakeMoveMessage, MuteMessage, PlaceStopperMessage, QueryGameMessage,
+    ResumeMessage, SendChatMessage, StartGameMessage, Watch,
+};
+use super::chat::Channel;
+use super::errors::{
+    ErrorMessage, WebsocketError, HANDSHAKE_ERROR, INTERNAL_ERROR_MESSAGE, MESSAGE_FORMAT_ERROR,
+    UNIMPLEMENTED_ERROR,
+};
+use super::protocol::{ClientMessage, MoveData, Resume, ServerMessage, PROTOCOL_VERSION};
+use crate::api::errors::APIError;
+use crate::api::responses::GetGameAnalysisResponse;
 use crate::db::model::SlimUser;
-use crate::graph::models::Move;
+use actix::dev::ToEnvelope;
 use actix::prelude::*;
 use actix_web_actors::ws;
-use hashbrown::HashMap;
-use serde::Serialize;
 use serde_json::to_string;
 use std::time::{Duration, Instant};
 use uuid::Uuid;
@@ -15,34 +24,6 @@ const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 // How long before lack of client response causes a timeout
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
 
-#[derive(Serialize, Clone, Debug)]
-struct ServerMessage<D> {
-    pub action: u8,
-    pub data: D,
-}
-
-#[derive(Serialize, Clone, Debug)]
-struct SimpleServerMessage<'a> {
-    pub action: u8,
-    pub data: HashMap<&'a str, String>,
-}
-
-
-#[derive(Serialize, Clone, Debug)]
-struct ServerListMessage<T> {
-    pub action: u8,
-    pub data: Vec<T>,
-}
-
-// response specific structs
-#[derive(Serialize)]
-pub struct QueryGameResponse {
-    name: String,
-    description: String,
-    state: i32,
-    players: Vec<(Uuid, String)>,
-}
-
 // Session specific struct
 pub struct WsGameSession {
     // unique session id (== user id)
@@ -56,6 +37,12 @@ pub struct WsGameSession {
     pub addr: Addr<GameServer>,
     // axtix identity bound
     pub uid: SlimUser,
+    // protocol version of the client (None until the handshake, see ws/protocol)
+    pub version: Option<u16>,
+    // reconnect token of the seat (see ws/protocol Resume)
+    pub token: Uuid,
+    // read-only session of a public game (see ws/routes spectator_route)
+    pub spectator: bool,
 }
 
 impl Actor for WsGameSession {
@@ -67,6 +54,10 @@ impl Actor for WsGameSession {
         // we'll start heartbeat process on session start.
         self.hb(ctx);
 
+        if self.spectator {
+            return self.watch(ctx);
+        }
+
         // register address for server actor
         let addr = ctx.address();
         self.addr
@@ -77,9 +68,14 @@ impl Actor for WsGameSession {
             .into_actor(self)
             .then(|res, act, ctx| {
                 match res {
-                    Ok(res) => {
-                        act.id =
-                            res.expect("The game server failed to acquire a session fatal error");
+                    Ok(Ok((id, token))) => {
+                        act.id = id;
+                        act.token = token;
+                    }
+                    // e.g. the user was kicked (see ws/chat)
+                    Ok(Err(why)) => {
+                        send_error(ctx, why.into());
+                        ctx.stop()
                     }
                     // something is wrong with game server
                     Err(why) => {
@@ -100,11 +96,20 @@ impl Actor for WsGameSession {
 }
 
 // Handle messages from game server, we simply send it to peer websocket
-impl Handler<Message> for WsGameSession {
+impl Handler<ServerMessage> for WsGameSession {
     type Result = ();
 
-    fn handle(&mut self, msg: Message, ctx: &mut Self::Context) {
-        ctx.text(to_string(&msg).expect("The GameServer sends corrupt messages"));
+    fn handle(&mut self, msg: ServerMessage, ctx: &mut Self::Context) {
+        // clients that didn't finish the handshake might not understand them
+        if self.version.is_some() {
+            send(ctx, &msg);
+        }
+
+        // the host kicked the user (see ws/chat)
+        if let ServerMessage::Kicked = msg {
+            ctx.close(Some((ws::CloseCode::Policy, "Kicked by the host").into()));
+            ctx.stop();
+        }
     }
 }
 
@@ -127,101 +132,10 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsGameSession {
             ws::Message::Pong(_) => {
                 self.hb = Instant::now();
             }
-            ws::Message::Text(text) => {
-                match serde_json::from_str::<Message>(&text) {
-                    Ok(action) => {
-                        // see action mapping in actors::ClientMessage
-                        match action.action {
-                            1 => {
-                                self.addr.send(QueryGameMessage { gid: self.game })
-                            .into_actor(self)
-                            // Result<(String, String, i32), APIError>
-                            .then(|res, _, ctx| {
-                                let _ = match res {
-                                     Ok(result) => {
-                                        let (name, description, state, players) = match result {
-                                                Ok(users) => users,
-                                                Err(_) => {
-                                                    ctx.stop();
-                                                    return fut::ready(());
-                                                }
-                                        };
-
-                                        
-
-                                        let message = ServerMessage {
-                                            action: 1,
-                                            data: QueryGameResponse {
-                                                name, description, state, players
-                                            }
-                                        };
-
-                                        let data = match serde_json::to_string(&message) {
-                                                Ok(data) => data,
-                                                Err(_) => {
-                                                    ctx.stop();
-                                                    return fut::ready(());
-                                                }
-                                        };
-
-                                        ctx.text(data);
-
-                                    }
-                                    // something is wrong with game server
-                                    Err(why) => {
-                                        eprintln!("The gamserver crashed or game was closed: {:?}", why);
-                                        ctx.stop()
-                                    }
-                                };
-                                fut::ready(())
-
-                            })
-                            .wait(ctx);
-                            }
-                            2 => {
-                                let parsed_move = match Move::from_action(action.data) {
-                                    Ok(parsed_move) => parsed_move,
-                                    Err(e) => {
-                                        ctx.text(e.to_string());
-                                        return;
-                                    }
-                                };
-
-                                self.addr.send(MakeMoveMessage { action: parsed_move.action, gid: self.game, uid: self.uid.id })
-                            .into_actor(self)
-                            .then(|res, _, ctx| {
-                                let _ = match res {
-                                     Ok(result) => {
-                                        let state = match result {
-                                                Ok(state) => state,
-                                                Err(_) => {
-                                                    ctx.stop();
-                                                    return fut::ready(());
-                                                }
-                                        };
-
-                                        println!("{:?}", state);
-
-                                        ctx.text("LOL");
-
-                                    }
-                                    // something is wrong with game server
-                                    Err(why) => {
-                                        eprintln!("The gamserver crashed or game was closed: {:?}", why);
-                                        ctx.stop()
-                                    }
-                                };
-                                fut::ready(())
-
-                            })
-                            .wait(ctx);
-                            }
-                            _ => ctx.text(UNIMPLEMENTED_ERROR.clone()),
-                        };
-                    }
-                    Err(_) => ctx.text(MESSAGE_FORMAT_ERROR.clone()),
-                }
-            }
+            ws::Message::Text(text) => match serde_json::from_str::<ClientMessage>(&text) {
+                Ok(message) => self.receive(message, ctx),
+                Err(_) => ctx.text(MESSAGE_FORMAT_ERROR.clone()),
+            },
             ws::Message::Binary(_) => ctx.text(UNIMPLEMENTED_ERROR.clone()),
             ws::Message::Close(reason) => {
                 ctx.close(reason);
@@ -235,7 +149,279 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsGameSession {
     }
 }
 
+// serialize and send a message to the client
+fn send(ctx: &mut ws::WebsocketContext<WsGameSession>, message: &ServerMessage) {
+    match to_string(message) {
+        Ok(text) => ctx.text(text),
+        Err(_) => ctx.text(INTERNAL_ERROR_MESSAGE.clone()),
+    }
+}
+
+fn send_error(ctx: &mut ws::WebsocketContext<WsGameSession>, error: WebsocketError) {
+    send(ctx, &ServerMessage::Error(ErrorMessage::new(error)));
+}
+
+// spectators may be delayed (see ws/actor views) -> everything but watching and chatting either
+// changes the game or reveals the live position
+fn spectator_allowed(message: &ClientMessage) -> bool {
+    matches!(
+        message,
+        ClientMessage::Hello { .. }
+            | ClientMessage::QueryGame
+            | ClientMessage::Chat { .. }
+            | ClientMessage::ChatHistory
+    )
+}
+
 impl WsGameSession {
+    // handle a message of the client (see ws/protocol ClientMessage)
+    fn receive(&mut self, message: ClientMessage, ctx: &mut ws::WebsocketContext<Self>) {
+        match (message, self.version) {
+            (ClientMessage::Hello { version, resume }, _) if version == PROTOCOL_VERSION => {
+                match resume {
+                    Some(resume) => self.resume(version, resume, ctx),
+                    None => {
+                        self.version = Some(version);
+                        send(ctx, &self.welcome(false));
+                    }
+                }
+            }
+            (ClientMessage::Hello { version, .. }, _) => {
+                let error = WebsocketError::VersionError { version };
+                let reason = error.to_string();
+                send_error(ctx, error);
+                ctx.close(Some((ws::CloseCode::Protocol, reason).into()));
+                ctx.stop();
+            }
+            (_, None) => ctx.text(HANDSHAKE_ERROR.clone()),
+            (message, _) if self.spectator && !spectator_allowed(&message) => {
+                let message = "Spectators can only watch the game and chat".to_owned();
+                send_error(ctx, WebsocketError::AuthorizationError { message });
+            }
+            (ClientMessage::QueryGame, _) => {
+                let msg = QueryGameMessage {
+                    gid: self.game,
+                    spectator: self.spectator,
+                };
+                self.request(msg, ctx, |info| Some(ServerMessage::Game(info)));
+            }
+            (ClientMessage::MakeMove { action }, _) => {
+                let action = match action.resolve() {
+                    Ok(action) => action,
+                    Err(why) => return send_error(ctx, why.into()),
+                };
+
+                let msg = MakeMoveMessage {
+                    action,
+                    gid: self.game,
+                    uid: self.uid.id,
+                };
+                self.request(msg, ctx, move |_| {
+                    Some(ServerMessage::MoveAccepted {
+                        action: MoveData::new(&action),
+                    })
+                });
+            }
+            (ClientMessage::PlaceStopper { dest, figure }, _) => {
+                let dest = match dest.resolve() {
+                    Ok(dest) => dest,
+                    Err(why) => return send_error(ctx, why.into()),
+                };
+
+                let msg = PlaceStopperMessage {
+                    dest,
+                    figure,
+                    gid: self.game,
+                    uid: self.uid.id,
+                };
+                // the room (including this session) is notified by the game server
+                self.request(msg, ctx, |_| None);
+            }
+            (ClientMessage::StartGame, _) => {
+                let msg = StartGameMessage {
+                    gid: self.game,
+                    uid: self.uid.id,
+                };
+                self.request(msg, ctx, |_| None);
+            }
+            (ClientMessage::LegalMoves { figure }, _) => {
+                let msg = LegalMovesMessage {
+                    gid: self.game,
+                    figure,
+                };
+                self.request(msg, ctx, move |destinations| {
+                    Some(ServerMessage::LegalMoves {
+                        figure,
+                        destinations,
+                    })
+                });
+            }
+            (ClientMessage::Analysis, _) => {
+                self.request(
+                    AnalysisMessage { gid: self.game },
+                    ctx,
+                    |(position, analysis)| {
+                        Some(ServerMessage::Analysis(GetGameAnalysisResponse::new(
+                            &position, analysis,
+                        )))
+                    },
+                );
+            }
+            (ClientMessage::Chat { message }, _) => {
+                let msg = SendChatMessage {
+                    uid: self.uid.id,
+                    username: self.uid.username.clone(),
+                    gid: self.game,
+                    channel: Channel::of(self.spectator),
+                    message,
+                };
+                // the channel (including this session) gets the message from the game server
+                self.request(msg, ctx, |_| None);
+            }
+            (ClientMessage::ChatHistory, _) => {
+                let msg = ChatHistoryMessage {
+                    gid: self.game,
+                    channel: Channel::of(self.spectator),
+                };
+                self.request(msg, ctx, |messages| {
+                    Some(ServerMessage::ChatHistory { messages })
+                });
+            }
+            (ClientMessage::Mute { user }, _) => self.mute(user, true, ctx),
+            (ClientMessage::Unmute { user }, _) => self.mute(user, false, ctx),
+            (ClientMessage::Kick { user }, _) => {
+                let msg = KickMessage {
+                    uid: self.uid.id,
+                    gid: self.game,
+                    user,
+                };
+                self.request(msg, ctx, |_| None);
+            }
+        }
+    }
+
+    fn mute(&self, user: Uuid, muted: bool, ctx: &mut ws::WebsocketContext<Self>) {
+        let msg = MuteMessage {
+            uid: self.uid.id,
+            gid: self.game,
+            user,
+            muted,
+        };
+        self.request(msg, ctx, |_| None);
+    }
+
+    // register as spectator of the game
+    fn watch(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        let msg = Watch {
+            addr: ctx.address().recipient(),
+            uid: self.uid.id,
+            gid: self.game,
+        };
+
+        self.addr
+            .send(msg)
+            .into_actor(self)
+            .then(|res, act, ctx| {
+                match res {
+                    Ok(Ok(id)) => act.id = id,
+                    Ok(Err(why)) => {
+                        send_error(ctx, why.into());
+                        ctx.stop()
+                    }
+                    // something is wrong with game server
+                    Err(why) => {
+                        eprintln!("The gamserver crashed: {:?}", why);
+                        ctx.stop()
+                    }
+                }
+                fut::ready(())
+            })
+            .wait(ctx);
+    }
+
+    fn welcome(&self, resumed: bool) -> ServerMessage {
+        ServerMessage::Welcome {
+            version: PROTOCOL_VERSION,
+            game: self.game,
+            token: self.token,
+            resumed,
+        }
+    }
+
+    /*
+    Take over the seat of an earlier session and replay the events it missed.
+    The handshake is finished afterwards, so newer events are sent after the replayed ones
+    */
+    fn resume(&self, version: u16, resume: Resume, ctx: &mut ws::WebsocketContext<Self>) {
+        let msg = ResumeMessage {
+            id: self.id,
+            uid: self.uid.id,
+            gid: self.game,
+            token: resume.token,
+            sequence: resume.sequence,
+        };
+
+        self.addr
+            .send(msg)
+            .into_actor(self)
+            .then(move |res, act, ctx| {
+                match res {
+                    Ok(events) => {
+                        act.version = Some(version);
+                        if events.is_some() {
+                            act.token = resume.token;
+                        }
+
+                        send(ctx, &act.welcome(events.is_some()));
+                        for event in events.unwrap_or_default() {
+                            send(ctx, &ServerMessage::Event(event));
+                        }
+                    }
+                    // something is wrong with game server
+                    Err(why) => {
+                        eprintln!("The gamserver crashed or game was closed: {:?}", why);
+                        ctx.stop()
+                    }
+                };
+                fut::ready(())
+            })
+            .wait(ctx);
+    }
+
+    /*
+    Send a request to the game server and reply with the message built from its result.
+    Rejected requests are answered with an error
+    */
+    fn request<M, T, F>(&self, msg: M, ctx: &mut ws::WebsocketContext<Self>, reply: F)
+    where
+        M: Message<Result = Result<T, APIError>> + Send + 'static,
+        T: Send + 'static,
+        F: FnOnce(T) -> Option<ServerMessage> + 'static,
+        GameServer: Handler<M>,
+        Context<GameServer>: ToEnvelope<GameServer, M>,
+    {
+        self.addr
+            .send(msg)
+            .into_actor(self)
+            .then(|res, _, ctx| {
+                match res {
+                    Ok(Ok(result)) => {
+                        if let Some(message) = reply(result) {
+                            send(ctx, &message);
+                        }
+                    }
+                    Ok(Err(why)) => send_error(ctx, why.into()),
+                    // something is wrong with game server
+                    Err(why) => {
+                        eprintln!("The gamserver crashed or game was closed: {:?}", why);
+                        ctx.stop()
+                    }
+                };
+                fut::ready(())
+            })
+            .wait(ctx);
+    }
+
     // helper method that sends ping to client every second.
     //
     // also this method checks heartbeats from client
@@ -260,3 +446,36 @@ impl WsGameSession {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed(raw: &str) -> bool {
+        spectator_allowed(&serde_json::from_str::<ClientMessage>(raw).expect("valid message"))
+    }
+
+    #[test]
+    fn spectators_dont_see_the_live_position() {
+        assert!(!allowed(r#"{"type": "legal_moves", "figure": 1}"#));
+        assert!(!allowed(r#"{"type": "analysis"}"#));
+    }
+
+    #[test]
+    fn spectators_dont_change_the_game() {
+        assert!(!allowed(r#"{"type": "make_move", "move": "1:f-a"}"#));
+        assert!(!allowed(r#"{"type": "place_stopper", "dest": "ag3"}"#));
+        assert!(!allowed(r#"{"type": "start_game"}"#));
+        assert!(!allowed(
+            r#"{"type": "kick", "user": "936da01f-9abd-4d9d-80c7-02af85c822a8"}"#
+        ));
+    }
+
+    #[test]
+    fn spectators_watch_and_chat() {
+        assert!(allowed(r#"{"type": "hello", "version": 2}"#));
+        assert!(allowed(r#"{"type": "query_game"}"#));
+        assert!(allowed(r#"{"type": "chat", "message": "gg"}"#));
+        assert!(allowed(r#"{"type": "chat_history"}"#));
+    }
+}
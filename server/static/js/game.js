@@ -13,10 +13,18 @@ import {
 } from "./utils.js";
 
 const SCALE = 1000;
+// see server/src/ws/protocol
+const PROTOCOL_VERSION = 2;
+// delay between reconnects (the server keeps the seat for a minute)
+const RECONNECT_DELAY = 3000;
 
 class Game {
   constructor(url) {
     this.url = url;
+    // sequence of the last applied event (see server/src/ws/protocol Event)
+    this.sequence = 0;
+    // reconnect token of the session (see server/src/ws/protocol Resume)
+    this.token = undefined;
   }
 
   create_modal() {
@@ -65,7 +73,39 @@ class Game {
     // those are done first as they don't rely on external data for creation
     this.draw_board();
     this.create_modal();
+    this.bind_chat();
+    this.connect();
+  }
+
+  bind_chat() {
+    this.chat = {
+      list: document.getElementById("game-chat"),
+      form: document.getElementById("game-chat-form"),
+      input: document.getElementById("game-chat-input"),
+    };
 
+    this.chat.form.onsubmit = (event) => {
+      event.preventDefault();
+      if (this.chat.input.value.trim() !== "") {
+        this.send({ type: "chat", message: this.chat.input.value });
+        this.chat.input.value = "";
+      }
+    };
+  }
+
+  add_chat_message(entry) {
+    let item = document.createElement("li");
+    let author = document.createElement("strong");
+    author.textContent = `${entry.username}: `;
+    item.appendChild(author);
+    item.appendChild(document.createTextNode(entry.message));
+    item.title = new Date(entry.sent * 1000).toLocaleString();
+
+    this.chat.list.appendChild(item);
+    this.chat.list.scrollTop = this.chat.list.scrollHeight;
+  }
+
+  connect() {
     // INFO: Change for production
     if (this.url !== undefined) {
       this.socket = new WebSocket(this.url);
@@ -83,6 +123,7 @@ class Game {
     console.log(this.reference);
     // set new progress
     this.reference.loading.content.innerHTML = "Connected to Websocket";
+    this.reference.loading.progress.style.backgroundColor = "";
     this.reference.loading.progress.style.width = "25";
     this.reference.loading.progress.setAttribute("aria-valuenow", "25");
 
@@ -91,11 +132,16 @@ class Game {
   }
 
   startup() {
-    // start intial setup
-    this.socket.send(JSON.stringify({ action: 0, data: {} }));
-    this.socket.send(JSON.stringify({ action: 1, data: {} }));
+    // handshake (see server/src/ws/protocol)
+    let hello = { type: "hello", version: PROTOCOL_VERSION };
+    if (this.token !== undefined) {
+      hello.resume = { token: this.token, sequence: this.sequence };
+    }
+    this.send(hello);
+  }
 
-    this.loading.modal.hide();
+  send(message) {
+    this.socket.send(JSON.stringify(message));
   }
 
   update_users(users) {
@@ -178,22 +224,88 @@ class Game {
     this.board.drawFigure(data.figure);
   }
 
+  apply_event(data) {
+    // missed events or no game loaded yet -> load the whole game again
+    if (this.meta === undefined || data.sequence !== this.sequence + 1) {
+      this.send({ type: "query_game" });
+      return;
+    }
+    this.sequence = data.sequence;
+
+    switch (data.event) {
+      case "move_made":
+      case "stopper_placed":
+        this.update_board(data.move);
+        break;
+
+      case "game_started":
+      case "turn_changed":
+      case "game_finished":
+        this.meta.state = data.state;
+        this.update_metadata(this.meta);
+        break;
+
+      default:
+        console.debug(data);
+        break;
+    }
+  }
+
   onmessage(event) {
     let data = JSON.parse(event.data);
-    switch (data.action) {
-      case 1:
-        this.reference.update_metadata(data.data);
+    switch (data.type) {
+      case "welcome":
+        this.reference.token = data.token;
+        // missed events are replayed after welcome when the session was resumed
+        if (!data.resumed || this.reference.meta === undefined) {
+          this.reference.send({ type: "query_game" });
+        }
+        this.reference.send({ type: "chat_history" });
+        this.reference.loading.modal.hide();
+        break;
+
+      case "game":
+        this.reference.sequence = data.sequence;
+        this.reference.update_metadata(data);
+        break;
+
+      case "event":
+        this.reference.apply_event(data);
+        break;
+
+      case "chat":
+        this.reference.add_chat_message(data);
+        break;
+
+      case "chat_history":
+        this.reference.chat.list.innerHTML = "";
+        data.messages.forEach((entry) => this.reference.add_chat_message(entry));
         break;
 
-      case 2:
-        this.reference.update_board(data.data);
+      case "muted":
+        this.reference.chat.input.disabled = data.muted;
+        create_alert(
+          2,
+          data.muted
+            ? "The host muted you in the chat"
+            : "The host unmuted you in the chat"
+        );
+        break;
+
+      case "kicked":
+        create_alert(2, "The host removed you from this game");
+        break;
+
+      case "error":
+        create_alert("Error", "danger", data.message);
         break;
+
       default:
         console.debug(data);
         create_alert(
           "Protocol Error",
           "danger",
-          "Server responded with an unknown action code"
+          "Server responded with an unknown message type"
         );
         break;
     }
@@ -205,6 +317,12 @@ class Game {
     this.reference.loading.progress.style.backgroundColor = "red";
     this.reference.loading.progress.style.width = "100";
     this.reference.loading.progress.setAttribute("aria-valuenow", "100");
+
+    // the server rejected our protocol version or kicked us -> reconnecting won't help
+    if (event.code !== 1002 && event.code !== 1008) {
+      this.reference.loading.modal.show();
+      setTimeout(() => this.reference.connect(), RECONNECT_DELAY);
+    }
   }
 }
 
@@ -228,6 +346,12 @@ document.addEventListener("DOMContentLoaded", function () {
    This doesn't do authentication as the request is handled with SessionCookies
    */
 
+  // spectators watch a public game (see server/src/ws/routes spectator_route)
+  let socket = document.getElementById("penta").dataset.socket;
+  if (socket !== undefined) {
+    instance.url = `ws://${location.host}${socket}`;
+  }
+
   instance.open();
   globalThis.instance = instance;
 
@@ -0,0 +1,222 @@
+use pentagame::graph::{
+    board::DEFINITION,
+    graph::{GraphState, GRAPH},
+    models::{FIELD, HAND, MOVE, OFF_BOARD},
+    movegen::legal_moves,
+    rules::{self, Turn},
+};
+use proptest::prelude::*;
+
+/*
+Property based tests of the board (see graph/graph) and the rules engine (see graph/rules)
+States are generated by playing random legal moves from the initial state of 2-5 players
+*/
+
+// every field of the board in sorted order
+fn fields() -> Vec<FIELD> {
+    let mut fields = GRAPH.vertices.keys().copied().collect::<Vec<FIELD>>();
+    fields.sort();
+    fields
+}
+
+// state and turn after playing the legal move choices[n] % amount of legal moves in every ply
+fn play(players: u8, choices: &[u16]) -> (GraphState, Turn) {
+    let mut state = GraphState::initial(players).unwrap();
+    let mut turn = Turn::Move(1);
+
+    for choice in choices {
+        let pid = match turn {
+            Turn::Move(pid) | Turn::Placement(pid) => pid,
+            _ => break,
+        };
+
+        let moves = legal_moves(&state, pid).unwrap();
+        if moves.is_empty() {
+            break;
+        }
+
+        let action = moves[*choice as usize % moves.len()];
+        state = apply(&state, pid, &action).unwrap();
+        turn = match rules::ranking(&state, players) {
+            Some(ranking) => Turn::Finished(rules::winners(&ranking)),
+            None => rules::next_turn(&state, pid, players),
+        };
+    }
+
+    (state, turn)
+}
+
+// apply a move or placement
+fn apply(state: &GraphState, pid: u8, action: &MOVE) -> Option<GraphState> {
+    if rules::split(action).1 == HAND {
+        rules::apply_placement(state, action).ok()
+    } else {
+        rules::apply_move(state, pid, action)
+            .ok()
+            .map(|outcome| outcome.state)
+    }
+}
+
+// figures of the state (figure ids of empty seats aren't part of it)
+fn pieces(state: &GraphState) -> Vec<u8> {
+    (1..=35_u8)
+        .filter(|figure| state.locate(*figure).is_some())
+        .collect()
+}
+
+fn field() -> impl Strategy<Value = FIELD> {
+    let fields = fields();
+    (0..fields.len()).prop_map(move |index| fields[index])
+}
+
+// arbitrary locations mixed with moves between fields of the board
+fn locations() -> impl Strategy<Value = [i16; 6]> {
+    prop_oneof![
+        any::<[i16; 6]>(),
+        (field(), field())
+            .prop_map(|(src, dest)| [src[0], src[1], src[2], dest[0], dest[1], dest[2]]),
+    ]
+}
+
+fn game() -> impl Strategy<Value = (u8, Vec<u16>)> {
+    (2..=5_u8, prop::collection::vec(any::<u16>(), 0..40))
+}
+
+#[test]
+fn board_has_every_field_of_the_definition() {
+    assert_eq!(fields().len(), DEFINITION.fields());
+
+    for edge in &DEFINITION.edges {
+        for stop in 1..=edge.stops {
+            assert!(GRAPH.vertices.contains_key(&[edge.from, stop, edge.to]));
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn edges_are_symmetric(src in field(), dest in field()) {
+        let forward = GRAPH.edges[&src].contains(&dest);
+        let backward = GRAPH.edges[&dest].contains(&src);
+        prop_assert_eq!(forward, backward);
+    }
+
+    #[test]
+    fn fields_have_fixed_degrees(field in field()) {
+        let degree = GRAPH.edges[&field].len();
+        match field {
+            // junctions and corners are connected to two junctions and two corners
+            [_, 0, 0] => prop_assert_eq!(degree, 4),
+            _ => prop_assert_eq!(degree, 2),
+        }
+    }
+
+    #[test]
+    fn every_field_is_reachable(src in field()) {
+        let reachable = GRAPH.reachable(&src).unwrap();
+        prop_assert_eq!(reachable.len(), GRAPH.vertices.len() - 1);
+        prop_assert!(!reachable.contains(&src));
+
+        let distances = GRAPH.distances(&src).unwrap();
+        prop_assert!(fields().iter().all(|field| distances.contains_key(field)));
+    }
+
+    #[test]
+    fn shortest_paths_are_connected(src in field(), dest in field()) {
+        let path = GRAPH.shortest_path(&src, &dest).unwrap().unwrap();
+        prop_assert_eq!(path[0], src);
+        prop_assert_eq!(path[path.len() - 1], dest);
+        prop_assert_eq!(path.len() as u16 - 1, GRAPH.distances(&src).unwrap()[&dest]);
+
+        for step in path.windows(2) {
+            prop_assert!(GRAPH.edges[&step[0]].contains(&step[1]));
+        }
+    }
+
+    #[test]
+    fn legal_moves_keep_every_piece((players, choices) in game(), choice in any::<u16>()) {
+        let (state, turn) = play(players, &choices);
+        let pid = match turn {
+            Turn::Move(pid) | Turn::Placement(pid) => pid,
+            _ => return Ok(()),
+        };
+
+        let moves = legal_moves(&state, pid).unwrap();
+        prop_assume!(!moves.is_empty());
+        let next = apply(&state, pid, &moves[choice as usize % moves.len()]).unwrap();
+
+        prop_assert_eq!(pieces(&next), pieces(&state));
+        prop_assert_eq!(pieces(&next).len(), 5 * players as usize + 10);
+
+        // only the starting corners hold more than one figure (swaps may add foreign figures)
+        for field in fields() {
+            if next.occupants(&field).len() > 1 {
+                prop_assert!((5..=9).contains(&field[0]) && field[1] == 0);
+            }
+        }
+
+        // every figure that left the board stays off it
+        for figure in pieces(&state) {
+            if state.locate(figure) == Some(OFF_BOARD) && figure <= 25 {
+                prop_assert_eq!(next.locate(figure), Some(OFF_BOARD));
+            }
+        }
+    }
+
+    #[test]
+    fn validation_never_panics(
+        (players, choices) in game(),
+        locations in locations(),
+        figure in prop_oneof![any::<u8>(), 1..=35_u8],
+        pid in prop_oneof![any::<u8>(), 1..=5_u8],
+    ) {
+        let (state, _) = play(players, &choices);
+        let action: MOVE = (locations, figure);
+
+        let _ = rules::apply_placement(&state, &action);
+        if let Ok(outcome) = rules::apply_move(&state, pid, &action) {
+            // everything the rules engine accepts is generated by the move generation
+            prop_assert!(legal_moves(&state, pid).unwrap().contains(&action));
+            prop_assert_eq!(pieces(&outcome.state), pieces(&state));
+        }
+
+        let (_, src, dest) = rules::split(&action);
+        let _ = GRAPH.validate(&src, &dest);
+        let _ = GRAPH.shortest_path(&src, &dest);
+    }
+
+    #[test]
+    fn legal_moves_are_accepted((players, choices) in game()) {
+        let (state, turn) = play(players, &choices);
+        let pid = match turn {
+            Turn::Move(pid) | Turn::Placement(pid) => pid,
+            _ => return Ok(()),
+        };
+
+        for action in legal_moves(&state, pid).unwrap() {
+            prop_assert!(apply(&state, pid, &action).is_some());
+        }
+    }
+
+    #[test]
+    fn passing_skips_the_passing_player((players, choices) in game()) {
+        let (state, turn) = play(players, &choices);
+        let pid = match turn {
+            Turn::Move(pid) => pid,
+            _ => return Ok(()),
+        };
+
+        match rules::pass_turn(&state, pid, players) {
+            Some(Turn::Move(next)) => {
+                prop_assert_ne!(next, pid);
+                prop_assert!(!legal_moves(&state, next).unwrap().is_empty());
+            }
+            Some(turn) => prop_assert!(false, "unexpected turn {:?}", turn),
+            None => {
+                for other in (1..=players).filter(|other| *other != pid) {
+                    prop_assert!(legal_moves(&state, other).unwrap().is_empty());
+                }
+            }
+        }
+    }
+}